@@ -0,0 +1,486 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+    ops::Range,
+};
+
+use super::disasm::{self, Instruction};
+
+/// How control gets from the end of a block to one of its successors.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EdgeKind {
+    /// The next instruction, e.g. after a call returns.
+    Fallthrough,
+    Jump,
+    /// The instruction after a skip, when the skip doesn't happen.
+    NoSkip,
+    /// Two bytes further, when a skip happens.
+    Skip,
+    Call,
+    /// An entry of a `BNNN` jump table.
+    Table,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Edge {
+    pub target: u16,
+    pub kind: EdgeKind,
+}
+
+/// Instructions that always run one after the other.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BasicBlock {
+    pub start: u16,
+    /// Address after the last instruction.
+    pub end: u16,
+    pub successors: Vec<Edge>,
+}
+
+/// The code reachable from a `2NNN` target (or the entry point) without
+/// following calls.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Subroutine {
+    pub entry: u16,
+    /// Start addresses of its blocks.
+    pub blocks: Vec<u16>,
+    /// Whether a `00EE` can be reached, the entry point usually never returns.
+    pub returns: bool,
+}
+
+/// A `BNNN` jump, and the addresses it can land on.
+#[derive(Clone, Debug, PartialEq)]
+pub struct JumpTable {
+    pub address: u16,
+    pub base: u16,
+    pub targets: Vec<u16>,
+}
+
+/// An `FX55` or `FX33` that writes over instructions.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SelfModification {
+    pub address: u16,
+    pub written: Range<u16>,
+}
+
+/// What a static pass over a ROM could recover: which bytes are code and
+/// how it flows, and which are data.
+///
+/// Code is found by following every path from the load address. Jump tables
+/// are recognised when `BNNN` points to a run of `1NNN` jumps, and the value
+/// of I is only tracked within a block, from `ANNN`.
+#[derive(Clone, Debug)]
+pub struct Analysis {
+    pub entry: u16,
+    /// By start address.
+    pub blocks: BTreeMap<u16, BasicBlock>,
+    pub subroutines: Vec<Subroutine>,
+    pub jump_tables: Vec<JumpTable>,
+    pub self_modifications: Vec<SelfModification>,
+    /// Parts of the ROM that are never reached as code.
+    pub data: Vec<Range<u16>>,
+    memory: Vec<u8>,
+}
+
+impl Analysis {
+    pub fn analyze(rom: &[u8], load_address: u16) -> Analysis {
+        // Addresses are 16 bits, the rest of the ROM can't be reached.
+        let rom = &rom[..rom.len().min((u16::MAX - load_address) as usize)];
+        let mut memory = vec![0; load_address as usize + rom.len()];
+        memory[load_address as usize..].copy_from_slice(rom);
+        let rom_range = load_address..load_address + rom.len() as u16;
+
+        let mut analysis = Analysis {
+            entry: load_address,
+            blocks: BTreeMap::new(),
+            subroutines: vec![],
+            jump_tables: vec![],
+            self_modifications: vec![],
+            data: vec![],
+            memory,
+        };
+
+        let (code, leaders, call_targets) = analysis.trace(&rom_range);
+        analysis.build_blocks(&code, &leaders);
+        analysis.find_subroutines(&call_targets);
+        analysis.find_self_modifications(&code);
+        analysis.find_data(&code, &rom_range);
+        analysis
+    }
+
+    /// Follows every path from the entry point. Returns the addresses of the
+    /// instructions reached, the ones that start a block, and call targets.
+    fn trace(&mut self, rom: &Range<u16>) -> (BTreeSet<u16>, BTreeSet<u16>, BTreeSet<u16>) {
+        let mut code = BTreeSet::new();
+        let mut leaders = BTreeSet::from([self.entry]);
+        let mut call_targets = BTreeSet::new();
+        let mut pending = vec![self.entry];
+
+        while let Some(address) = pending.pop() {
+            if !rom.contains(&address) || !rom.contains(&(address + 1)) || !code.insert(address) {
+                continue;
+            }
+
+            let instruction = self.instruction(address);
+            if let Instruction::JumpOffset(base) = instruction {
+                let table = self.jump_table(address, base, rom);
+                leaders.extend(&table.targets);
+                pending.extend(&table.targets);
+                self.jump_tables.push(table);
+                continue;
+            }
+
+            let successors = Analysis::successors(address, instruction);
+            if successors.len() != 1 || successors[0].kind != EdgeKind::Fallthrough {
+                leaders.extend(successors.iter().map(|edge| edge.target));
+            }
+            for edge in &successors {
+                if edge.kind == EdgeKind::Call {
+                    call_targets.insert(edge.target);
+                }
+            }
+            pending.extend(successors.iter().map(|edge| edge.target));
+        }
+        (code, leaders, call_targets)
+    }
+
+    /// Where control can go after the instruction at `address`, except for
+    /// `BNNN` which needs the memory around it, see [`Analysis::jump_table`].
+    /// Addresses past `FFFF` are left out.
+    fn successors(address: u16, instruction: Instruction) -> Vec<Edge> {
+        let edge = |target: u16, kind: EdgeKind| Edge { target, kind };
+        let after = |offset: u16, kind: EdgeKind| {
+            address.checked_add(offset).map(|target| edge(target, kind))
+        };
+        match instruction {
+            Instruction::Jump(target) => vec![edge(target, EdgeKind::Jump)],
+            Instruction::Call(target) => [
+                Some(edge(target, EdgeKind::Call)),
+                after(2, EdgeKind::Fallthrough),
+            ]
+            .into_iter()
+            .flatten()
+            .collect(),
+            Instruction::Return | Instruction::Unknown(_) | Instruction::JumpOffset(_) => vec![],
+            Instruction::SkipEqual { .. }
+            | Instruction::SkipNotEqual { .. }
+            | Instruction::SkipEqualRegister { .. }
+            | Instruction::SkipNotEqualRegister { .. }
+            | Instruction::SkipKey { .. }
+            | Instruction::SkipNotKey { .. } => {
+                [after(2, EdgeKind::NoSkip), after(4, EdgeKind::Skip)]
+                    .into_iter()
+                    .flatten()
+                    .collect()
+            }
+            _ => after(2, EdgeKind::Fallthrough).into_iter().collect(),
+        }
+    }
+
+    /// `BNNN` usually lands in a table of `1NNN` jumps at NNN, indexed by V0.
+    /// Without one, NNN itself is the only target we can guess.
+    fn jump_table(&self, address: u16, base: u16, rom: &Range<u16>) -> JumpTable {
+        let mut targets: Vec<u16> = (base..rom.end)
+            .step_by(2)
+            .take_while(|entry| matches!(self.instruction(*entry), Instruction::Jump(_)))
+            .collect();
+        if targets.is_empty() {
+            targets.push(base);
+        }
+        JumpTable {
+            address,
+            base,
+            targets,
+        }
+    }
+
+    fn build_blocks(&mut self, code: &BTreeSet<u16>, leaders: &BTreeSet<u16>) {
+        let table_sites: BTreeMap<u16, Vec<u16>> = self
+            .jump_tables
+            .iter()
+            .map(|table| (table.address, table.targets.clone()))
+            .collect();
+
+        for &start in leaders.iter().filter(|leader| code.contains(leader)) {
+            let mut address = start;
+            let successors = loop {
+                let instruction = self.instruction(address);
+                if let Some(targets) = table_sites.get(&address) {
+                    break targets
+                        .iter()
+                        .map(|target| Edge {
+                            target: *target,
+                            kind: EdgeKind::Table,
+                        })
+                        .collect();
+                }
+
+                let successors = Analysis::successors(address, instruction);
+                let next = address + 2;
+                let falls_through = successors.len() == 1
+                    && successors[0].kind == EdgeKind::Fallthrough
+                    && successors[0].target == next;
+                if !falls_through || leaders.contains(&next) || !code.contains(&next) {
+                    break successors;
+                }
+                address = next;
+            };
+
+            self.blocks.insert(
+                start,
+                BasicBlock {
+                    start,
+                    end: address + 2,
+                    successors,
+                },
+            );
+        }
+    }
+
+    fn find_subroutines(&mut self, call_targets: &BTreeSet<u16>) {
+        for &entry in std::iter::once(&self.entry).chain(call_targets) {
+            if !self.blocks.contains_key(&entry) {
+                continue;
+            }
+
+            let mut blocks = BTreeSet::new();
+            let mut pending = vec![entry];
+            while let Some(start) = pending.pop() {
+                let Some(block) = self.blocks.get(&start) else {
+                    continue;
+                };
+                if !blocks.insert(start) {
+                    continue;
+                }
+                pending.extend(
+                    block
+                        .successors
+                        .iter()
+                        .filter(|edge| edge.kind != EdgeKind::Call)
+                        .map(|edge| edge.target),
+                );
+            }
+
+            let returns = blocks
+                .iter()
+                .any(|start| self.instruction(self.blocks[start].end - 2) == Instruction::Return);
+            self.subroutines.push(Subroutine {
+                entry,
+                blocks: blocks.into_iter().collect(),
+                returns,
+            });
+        }
+    }
+
+    /// Looks for `FX55` and `FX33` writing over code, with I set earlier in the block.
+    fn find_self_modifications(&mut self, code: &BTreeSet<u16>) {
+        let is_code =
+            |address: u16| code.contains(&address) || code.contains(&address.wrapping_sub(1));
+
+        for block in self.blocks.values() {
+            let mut i = None;
+            for address in (block.start..block.end).step_by(2) {
+                let written = match self.instruction(address) {
+                    Instruction::LoadI(value) => {
+                        i = Some(value);
+                        continue;
+                    }
+                    Instruction::Store { x } => i.map(|i| i..i + x as u16 + 1),
+                    Instruction::Bcd { .. } => i.map(|i| i..i + 3),
+                    Instruction::AddI { .. } | Instruction::LoadFont { .. } => {
+                        i = None;
+                        continue;
+                    }
+                    _ => continue,
+                };
+                // FX55 may move I depending on the interpreter.
+                let Some(written) = written else { continue };
+                i = None;
+
+                if written.clone().any(is_code) {
+                    self.self_modifications
+                        .push(SelfModification { address, written });
+                }
+            }
+        }
+    }
+
+    fn find_data(&mut self, code: &BTreeSet<u16>, rom: &Range<u16>) {
+        let mut start = None;
+        for address in rom.start..=rom.end {
+            let is_code = code.contains(&address) || code.contains(&address.wrapping_sub(1));
+            match (is_code || address == rom.end, start) {
+                (false, None) => start = Some(address),
+                (true, Some(data_start)) => {
+                    self.data.push(data_start..address);
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    pub fn instruction(&self, address: u16) -> Instruction {
+        Instruction::at(&self.memory, address)
+    }
+
+    /// The ROM as loaded in memory, after zeros up to the load address.
+    pub fn memory(&self) -> &[u8] {
+        &self.memory
+    }
+
+    /// The block holding the instruction at `address`.
+    pub fn block_at(&self, address: u16) -> Option<&BasicBlock> {
+        self.blocks
+            .range(..=address)
+            .next_back()
+            .map(|(_, block)| block)
+            .filter(|block| address < block.end)
+    }
+
+    /// Renders the graph in Graphviz DOT: one box per block with its
+    /// disassembly, grouped by subroutine, and the data regions on the side.
+    /// Blocks that modify code are filled in red.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph rom {\n");
+        dot.push_str("  node [shape=box fontname=\"monospace\"];\n");
+
+        let mut drawn = BTreeSet::new();
+        for subroutine in &self.subroutines {
+            let _ = writeln!(dot, "  subgraph cluster_{:04X} {{", subroutine.entry);
+            let _ = writeln!(dot, "    label=\"sub {:04X}\";", subroutine.entry);
+            for start in &subroutine.blocks {
+                if drawn.insert(*start) {
+                    self.write_block(&mut dot, &self.blocks[start], "    ");
+                }
+            }
+            dot.push_str("  }\n");
+        }
+        for block in self.blocks.values() {
+            if !drawn.contains(&block.start) {
+                self.write_block(&mut dot, block, "  ");
+            }
+        }
+
+        for block in self.blocks.values() {
+            for edge in &block.successors {
+                let style = match edge.kind {
+                    EdgeKind::Fallthrough => "",
+                    EdgeKind::Jump => " [style=bold]",
+                    EdgeKind::NoSkip => " [label=\"no skip\"]",
+                    EdgeKind::Skip => " [label=\"skip\"]",
+                    EdgeKind::Call => " [style=dashed label=\"call\"]",
+                    EdgeKind::Table => " [style=dotted label=\"table\"]",
+                };
+                let _ = writeln!(
+                    dot,
+                    "  b{:04X} -> b{:04X}{};",
+                    block.start, edge.target, style
+                );
+            }
+        }
+
+        for data in &self.data {
+            let _ = writeln!(
+                dot,
+                "  d{:04X} [shape=note label=\"data {:04X}-{:04X}\"];",
+                data.start,
+                data.start,
+                data.end - 1
+            );
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    fn write_block(&self, dot: &mut String, block: &BasicBlock, indent: &str) {
+        let mut label = String::new();
+        for address in (block.start..block.end).step_by(2) {
+            let _ = write!(
+                label,
+                "{:04X}  {:04X}  {}\\l",
+                address,
+                disasm::opcode_at(&self.memory, address),
+                self.instruction(address)
+            );
+        }
+        let modifies_code = self
+            .self_modifications
+            .iter()
+            .any(|site| (block.start..block.end).contains(&site.address));
+        let style = if modifies_code {
+            " style=filled fillcolor=\"#ffb0b0\""
+        } else {
+            ""
+        };
+        let _ = writeln!(
+            dot,
+            "{}b{:04X} [label=\"{}\"{}];",
+            indent, block.start, label, style
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_blocks_at_skips_and_calls() {
+        // 200: 3001 SE V0, 01    204: 2208 CALL 208    208: 00EE RET
+        // 202: 1200 JP 200       206: 1206 JP 206
+        let rom = [0x30, 0x01, 0x12, 0x00, 0x22, 0x08, 0x12, 0x06, 0x00, 0xEE];
+        let analysis = Analysis::analyze(&rom, 0x200);
+
+        let first = &analysis.blocks[&0x200];
+        assert_eq!(first.end, 0x202);
+        assert_eq!(
+            first.successors,
+            [
+                Edge {
+                    target: 0x202,
+                    kind: EdgeKind::NoSkip
+                },
+                Edge {
+                    target: 0x204,
+                    kind: EdgeKind::Skip
+                },
+            ]
+        );
+        assert_eq!(analysis.subroutines.len(), 2);
+        assert!(analysis.subroutines[1].returns);
+        assert!(analysis.data.is_empty());
+    }
+
+    #[test]
+    fn finds_jump_tables_and_data() {
+        // 200: B204 JP V0, 204    204: 1208 JP 208    206: 1208 JP 208
+        // 208: 1208 JP 208        20A: data
+        let rom = [
+            0xB2, 0x04, 0xFF, 0xFF, 0x12, 0x08, 0x12, 0x08, 0x12, 0x08, 0xAB,
+        ];
+        let analysis = Analysis::analyze(&rom, 0x200);
+        assert_eq!(analysis.jump_tables[0].targets, [0x204, 0x206, 0x208]);
+        assert_eq!(analysis.data, [0x202..0x204, 0x20A..0x20B]);
+    }
+
+    #[test]
+    fn finds_writes_over_code() {
+        // 200: A202 LD I, 202    202: F055 LD [I], V0
+        let rom = [0xA2, 0x02, 0xF0, 0x55];
+        let analysis = Analysis::analyze(&rom, 0x200);
+        assert_eq!(analysis.self_modifications[0].written, 0x202..0x203);
+    }
+
+    #[test]
+    fn code_up_to_ffff() {
+        // Skips at the very end of the address space, and a ROM too long
+        // for it, can't go past FFFF.
+        let rom = [0x30, 0x01, 0x30, 0x01, 0x30, 0x01, 0x30, 0x01];
+        let analysis = Analysis::analyze(&rom, 0xFFFA);
+        assert_eq!(analysis.memory().len(), 0xFFFF);
+        assert!(analysis.blocks.contains_key(&0xFFFC));
+
+        let analysis = Analysis::analyze(&vec![0; 0x20000], 0x200);
+        assert_eq!(analysis.memory().len(), 0xFFFF);
+    }
+}
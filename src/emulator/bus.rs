@@ -0,0 +1,123 @@
+//! What the CPU is wired to. Programs fetch instructions, read and write
+//! data through a [`Bus`], so memory-mapped hooks, larger address spaces
+//! or recordings of every access can be attached without changing the CPU.
+
+use super::cdp1802;
+
+pub trait Bus {
+    /// A read by the program, which may have side effects. `address` is
+    /// within [`Bus::memory`].
+    fn read(&mut self, address: u32) -> u8;
+    fn write(&mut self, address: u32, value: u8);
+
+    /// The memory as it is, without side effects: for the debugger, the
+    /// reports and the interpreter's own bulk accesses (loading programs,
+    /// MegaChip blits). Its length is the size of the address space.
+    fn memory(&self) -> &[u8];
+    fn memory_mut(&mut self) -> &mut [u8];
+}
+
+/// Plain RAM, the default.
+impl Bus for Vec<u8> {
+    fn read(&mut self, address: u32) -> u8 {
+        self[address as usize]
+    }
+
+    fn write(&mut self, address: u32, value: u8) {
+        self[address as usize] = value;
+    }
+
+    fn memory(&self) -> &[u8] {
+        self
+    }
+
+    fn memory_mut(&mut self) -> &mut [u8] {
+        self
+    }
+}
+
+/// Machine code called by `0NNN` goes through the same bus, with addresses
+/// wrapping around its size as on plain 1802 memory.
+impl cdp1802::Bus for dyn Bus {
+    fn read(&mut self, address: u16) -> u8 {
+        let size = self.memory().len() as u32;
+        Bus::read(self, address as u32 % size)
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        let size = self.memory().len() as u32;
+        Bus::write(self, address as u32 % size, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+    use crate::emulator::cpu::Cpu;
+
+    /// Plain RAM that logs every read and write made through the bus.
+    struct Recording {
+        memory: Vec<u8>,
+        reads: Rc<RefCell<Vec<u32>>>,
+        writes: Rc<RefCell<Vec<(u32, u8)>>>,
+    }
+
+    impl Bus for Recording {
+        fn read(&mut self, address: u32) -> u8 {
+            self.reads.borrow_mut().push(address);
+            self.memory[address as usize]
+        }
+
+        fn write(&mut self, address: u32, value: u8) {
+            self.writes.borrow_mut().push((address, value));
+            self.memory[address as usize] = value;
+        }
+
+        fn memory(&self) -> &[u8] {
+            &self.memory
+        }
+
+        fn memory_mut(&mut self) -> &mut [u8] {
+            &mut self.memory
+        }
+    }
+
+    #[test]
+    fn plain_ram_reads_back_what_was_written() {
+        let mut ram = vec![0; 0x10];
+        Bus::write(&mut ram, 0x0F, 0x42);
+        assert_eq!(Bus::read(&mut ram, 0x0F), 0x42);
+        assert_eq!(ram.memory()[0x0F], 0x42);
+    }
+
+    #[test]
+    fn machine_code_addresses_wrap_around_the_bus() {
+        let mut ram: Box<dyn Bus> = Box::new(vec![0; 0x1000]);
+        cdp1802::Bus::write(ram.as_mut(), 0x1005, 0x42);
+        assert_eq!(ram.memory()[0x005], 0x42);
+        assert_eq!(cdp1802::Bus::read(ram.as_mut(), 0xF005), 0x42);
+    }
+
+    #[test]
+    fn an_attached_bus_sees_fetches_and_writes() {
+        let reads = Rc::new(RefCell::new(vec![]));
+        let writes = Rc::new(RefCell::new(vec![]));
+        let mut cpu = Cpu::new();
+        // 60FE A300 F033: the digits of 254 at 300.
+        cpu.load_rom(&[0x60, 0xFE, 0xA3, 0x00, 0xF0, 0x33]);
+        cpu.set_bus(Box::new(Recording {
+            memory: vec![0; cpu.memory().len()],
+            reads: Rc::clone(&reads),
+            writes: Rc::clone(&writes),
+        }));
+
+        for _ in 0..3 {
+            cpu.run_instruction().unwrap();
+        }
+        assert_eq!(*reads.borrow(), [0x200, 0x201, 0x202, 0x203, 0x204, 0x205]);
+        assert_eq!(*writes.borrow(), [(0x302, 4), (0x301, 5), (0x300, 2)]);
+        assert_eq!(cpu.memory()[0x300..0x303], [2, 5, 4]);
+    }
+}
@@ -0,0 +1,157 @@
+use std::error::Error;
+
+use log::info;
+use serde_json::Value;
+
+use super::{fontset::FontSet, octo, options::EmulatorOptions, palette::Palette};
+
+/// GIF files start with `GIF87a` or `GIF89a`.
+pub fn is_cartridge(data: &[u8]) -> bool {
+    data.starts_with(b"GIF8")
+}
+
+/// An Octo "cartridge": a GIF with the program source and its settings
+/// hidden in the low bits of the pixels.
+pub struct Cartridge {
+    pub program: Vec<u8>,
+    options: Value,
+}
+
+impl Cartridge {
+    /// Decodes a cartridge and assembles the program it contains, to be
+    /// loaded at `program_start`.
+    pub fn decode(data: &[u8], program_start: u16) -> Result<Cartridge, Box<dyn Error>> {
+        let mut decoder = gif::DecodeOptions::new();
+        decoder.set_color_output(gif::ColorOutput::Indexed);
+        let mut decoder = decoder.read_info(data)?;
+
+        // Every pixel carries two bits of payload, most significant first.
+        let mut bits = vec![];
+        while let Some(frame) = decoder.read_next_frame()? {
+            bits.extend(frame.buffer.iter().map(|index| index & 0b11));
+        }
+        let byte = |index: usize| -> Option<u8> {
+            let pixels = bits.get(index * 4..index * 4 + 4)?;
+            Some(pixels.iter().fold(0, |byte, bits| (byte << 2) | bits))
+        };
+
+        let size = (0..4)
+            .map(byte)
+            .try_fold(0usize, |size, byte| Some((size << 8) | byte? as usize))
+            .ok_or("cartridge is too small")?;
+        let payload = (4..4 + size)
+            .map(byte)
+            .collect::<Option<Vec<u8>>>()
+            .ok_or("cartridge payload is truncated")?;
+
+        let json: Value = serde_json::from_slice(&payload)?;
+        let source = json["program"].as_str().ok_or("cartridge has no program")?;
+        let program = octo::assemble(source, program_start)?;
+        info!("Assembled Octo cartridge ({} bytes)", program.len());
+
+        Ok(Cartridge {
+            program,
+            options: json["options"].clone(),
+        })
+    }
+
+    /// Applies the settings embedded in the cartridge, leaving the ones it
+    /// doesn't mention untouched.
+    pub fn apply_options(&self, options: &mut EmulatorOptions) {
+        let flag = |name: &str| self.options[name].as_bool();
+        let color = |name: &str| {
+            self.options[name]
+                .as_str()
+                .and_then(|color| Palette::parse(&format!("{},000000", color)))
+                .map(|palette| palette.foreground)
+        };
+
+        if let Some(tickrate) = self.options["tickrate"].as_u64() {
+            options.instructions_per_frame = tickrate as u32;
+        }
+        if let Some(foreground) = color("fillColor") {
+            options.palette.foreground = foreground;
+        }
+        if let Some(background) = color("backgroundColor") {
+            options.palette.background = background;
+        }
+        if let Some(font) = self.options["fontStyle"].as_str().and_then(FontSet::parse) {
+            options.font = font;
+        }
+
+        let quirks = &mut options.quirks;
+        if let Some(shift) = flag("shiftQuirks") {
+            quirks.shift_ignores_vy = shift;
+        }
+        if let Some(load_store) = flag("loadStoreQuirks") {
+            quirks.load_store_keeps_i = load_store;
+        }
+        if let Some(jump) = flag("jumpQuirks") {
+            quirks.jump_uses_vx = jump;
+        }
+        if let Some(clip) = flag("clipQuirks") {
+            quirks.clip_sprites = clip;
+        }
+        if let Some(logic) = flag("logicQuirks") {
+            quirks.vf_reset = logic;
+        }
+        if let Some(vblank) = flag("vBlankQuirks") {
+            quirks.display_wait = vblank;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Hides `json` in a GIF the way Octo does: its size on 4 bytes, then
+    /// the bytes, 2 bits per pixel.
+    fn cartridge(json: &str) -> Vec<u8> {
+        let mut payload = (json.len() as u32).to_be_bytes().to_vec();
+        payload.extend_from_slice(json.as_bytes());
+        let pixels: Vec<u8> = payload
+            .iter()
+            .flat_map(|byte| [6, 4, 2, 0].map(|shift| (byte >> shift) & 0b11))
+            .collect();
+
+        let mut gif = vec![];
+        let palette = [0, 0, 0, 85, 85, 85, 170, 170, 170, 255, 255, 255];
+        let width = pixels.len() as u16;
+        let mut encoder = gif::Encoder::new(&mut gif, width, 1, &palette).unwrap();
+        let frame = gif::Frame::from_indexed_pixels(width, 1, pixels, None);
+        encoder.write_frame(&frame).unwrap();
+        drop(encoder);
+        gif
+    }
+
+    #[test]
+    fn decodes_and_assembles_the_program() {
+        let gif = cartridge(r#"{"program": ": main v0 := 1", "options": {}}"#);
+        assert!(is_cartridge(&gif));
+        let cartridge = Cartridge::decode(&gif, 0x200).unwrap();
+        assert_eq!(cartridge.program, [0x12, 0x02, 0x60, 0x01]);
+    }
+
+    #[test]
+    fn applies_the_options_it_mentions() {
+        let gif = cartridge(
+            r#"{"program": ": main", "options": {"tickrate": 20, "shiftQuirks": true, "fontStyle": "vip"}}"#,
+        );
+        let cartridge = Cartridge::decode(&gif, 0x200).unwrap();
+        let mut options = EmulatorOptions::default();
+        cartridge.apply_options(&mut options);
+        assert_eq!(options.instructions_per_frame, 20);
+        assert!(options.quirks.shift_ignores_vy);
+        assert!(options.quirks.vf_reset);
+        assert_eq!(options.font, FontSet::Vip);
+    }
+
+    #[test]
+    fn truncated_cartridges_are_errors() {
+        let mut gif = cartridge(r#"{"program": ": main"}"#);
+        assert!(Cartridge::decode(&gif[..gif.len() / 2], 0x200).is_err());
+        gif.truncate(6);
+        assert!(Cartridge::decode(&gif, 0x200).is_err());
+    }
+}
@@ -0,0 +1,439 @@
+//! The RCA CDP1802, the processor of the COSMAC VIP. CHIP-8 programs call
+//! 1802 machine code with `0NNN`.
+
+/// What the 1802 is wired to: memory, the devices on its I/O lines
+/// (`OUT 1`-`OUT 7`, `INP 1`-`INP 7`) and the EF1-EF4 flags.
+pub trait Bus {
+    fn read(&mut self, address: u16) -> u8;
+    fn write(&mut self, address: u16, value: u8);
+
+    fn output(&mut self, _port: u8, _value: u8) {}
+
+    fn input(&mut self, _port: u8) -> u8 {
+        0
+    }
+
+    /// Whether EF`n` (1 to 4) is asserted.
+    fn flag(&mut self, _n: u8) -> bool {
+        false
+    }
+}
+
+/// Plain memory with nothing on the I/O lines, addresses wrap around its size.
+impl Bus for [u8] {
+    fn read(&mut self, address: u16) -> u8 {
+        self[address as usize % self.len()]
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        let len = self.len();
+        self[address as usize % len] = value;
+    }
+}
+
+/// Machine cycles of most instructions, long branches and skips take 3.
+const CYCLES: u32 = 2;
+const LONG_CYCLES: u32 = 3;
+
+/// Registers of the 1802. The [`Bus`] is passed to every step, so memory can
+/// be shared with the CHIP-8 interpreter.
+#[derive(Clone, Debug, Default)]
+pub struct Cdp1802 {
+    /// 16 scratchpad registers of 16 bits.
+    pub r: [u16; 16],
+    /// The accumulator.
+    pub d: u8,
+    /// Carry / not borrow.
+    pub df: bool,
+    /// Which register is the program counter.
+    pub p: u8,
+    /// Which register points to data.
+    pub x: u8,
+    /// X and P saved by `MARK` or an interrupt.
+    pub t: u8,
+    /// Interrupt enable.
+    pub ie: bool,
+    /// Output flip-flop, drives the VIP's buzzer.
+    pub q: bool,
+    /// Machine cycles run so far.
+    pub cycles: u64,
+}
+
+impl Cdp1802 {
+    pub fn new() -> Self {
+        Self {
+            ie: true,
+            ..Default::default()
+        }
+    }
+
+    /// Jumps to the interrupt routine at R1, saving X and P in T.
+    pub fn interrupt(&mut self) {
+        self.t = (self.x << 4) | self.p;
+        self.x = 2;
+        self.p = 1;
+        self.ie = false;
+    }
+
+    /// Reads the byte after the opcode, for immediate instructions.
+    fn immediate<B: Bus + ?Sized>(&mut self, bus: &mut B) -> u8 {
+        let p = self.p as usize;
+        let value = bus.read(self.r[p]);
+        self.r[p] = self.r[p].wrapping_add(1);
+        value
+    }
+
+    fn rx(&self) -> u16 {
+        self.r[self.x as usize]
+    }
+
+    /// Adds `a`, `b` and `carry` into D, setting DF on overflow.
+    fn add(&mut self, a: u8, b: u8, carry: bool) {
+        let sum = a as u16 + b as u16 + carry as u16;
+        self.d = sum as u8;
+        self.df = sum > 0xFF;
+    }
+
+    /// Subtracts `b` from `a` into D, DF is cleared on borrow.
+    fn subtract(&mut self, a: u8, b: u8, borrow: bool) {
+        let difference = a as i16 - b as i16 - borrow as i16;
+        self.d = difference as u8;
+        self.df = difference >= 0;
+    }
+
+    /// The condition tested by short and long branches (the low 3 bits of
+    /// the opcode, bit 3 inverts it).
+    fn condition<B: Bus + ?Sized>(&self, opcode: u8, bus: &mut B) -> bool {
+        let condition = match opcode & 0x7 {
+            0 => true,
+            1 => self.q,
+            2 => self.d == 0,
+            3 => self.df,
+            flag => bus.flag(flag - 3),
+        };
+        condition != (opcode & 0x8 != 0)
+    }
+
+    /// Runs one instruction. Returns the machine cycles it took.
+    pub fn step<B: Bus + ?Sized>(&mut self, bus: &mut B) -> u32 {
+        let opcode = self.immediate(bus);
+        let n = (opcode & 0xF) as usize;
+        let p = self.p as usize;
+        let x = self.x as usize;
+
+        let cycles = match opcode >> 4 {
+            // IDL waits for an interrupt or DMA, which happen between frames.
+            0x0 if n == 0 => CYCLES,
+            0x0 => {
+                self.d = bus.read(self.r[n]);
+                CYCLES
+            }
+            0x1 => {
+                self.r[n] = self.r[n].wrapping_add(1);
+                CYCLES
+            }
+            0x2 => {
+                self.r[n] = self.r[n].wrapping_sub(1);
+                CYCLES
+            }
+            0x3 => {
+                // The branch replaces the low byte of the program counter.
+                let target = bus.read(self.r[p]);
+                if self.condition(opcode, bus) {
+                    self.r[p] = (self.r[p] & 0xFF00) | target as u16;
+                } else {
+                    self.r[p] = self.r[p].wrapping_add(1);
+                }
+                CYCLES
+            }
+            0x4 => {
+                self.d = bus.read(self.r[n]);
+                self.r[n] = self.r[n].wrapping_add(1);
+                CYCLES
+            }
+            0x5 => {
+                bus.write(self.r[n], self.d);
+                CYCLES
+            }
+            0x6 => {
+                match n {
+                    // IRX
+                    0x0 => self.r[x] = self.r[x].wrapping_add(1),
+                    0x1..=0x7 => {
+                        let value = bus.read(self.rx());
+                        bus.output(n as u8, value);
+                        self.r[x] = self.r[x].wrapping_add(1);
+                    }
+                    0x8 => {}
+                    _ => {
+                        self.d = bus.input(n as u8 - 0x8);
+                        bus.write(self.rx(), self.d);
+                    }
+                }
+                CYCLES
+            }
+            0x7 => {
+                self.op_7n(n, bus);
+                CYCLES
+            }
+            0x8 => {
+                self.d = self.r[n] as u8;
+                CYCLES
+            }
+            0x9 => {
+                self.d = (self.r[n] >> 8) as u8;
+                CYCLES
+            }
+            0xA => {
+                self.r[n] = (self.r[n] & 0xFF00) | self.d as u16;
+                CYCLES
+            }
+            0xB => {
+                self.r[n] = (self.r[n] & 0x00FF) | ((self.d as u16) << 8);
+                CYCLES
+            }
+            0xC => {
+                self.op_cn(opcode, bus);
+                LONG_CYCLES
+            }
+            0xD => {
+                self.p = n as u8;
+                CYCLES
+            }
+            0xE => {
+                self.x = n as u8;
+                CYCLES
+            }
+            _ => {
+                self.op_fn(n, bus);
+                CYCLES
+            }
+        };
+
+        self.cycles += cycles as u64;
+        cycles
+    }
+
+    /// Control and arithmetic with carry.
+    fn op_7n<B: Bus + ?Sized>(&mut self, n: usize, bus: &mut B) {
+        let x = self.x as usize;
+        let m = bus.read(self.rx());
+        match n {
+            // RET and DIS
+            0x0 | 0x1 => {
+                self.x = m >> 4;
+                self.p = m & 0xF;
+                self.r[x] = self.r[x].wrapping_add(1);
+                self.ie = n == 0x0;
+            }
+            // LDXA
+            0x2 => {
+                self.d = m;
+                self.r[x] = self.r[x].wrapping_add(1);
+            }
+            // STXD
+            0x3 => {
+                bus.write(self.rx(), self.d);
+                self.r[x] = self.r[x].wrapping_sub(1);
+            }
+            0x4 => self.add(m, self.d, self.df),
+            0x5 => self.subtract(m, self.d, !self.df),
+            // SHRC
+            0x6 => {
+                let carry = self.d & 1 != 0;
+                self.d = (self.d >> 1) | ((self.df as u8) << 7);
+                self.df = carry;
+            }
+            0x7 => self.subtract(self.d, m, !self.df),
+            // SAV
+            0x8 => bus.write(self.rx(), self.t),
+            // MARK
+            0x9 => {
+                self.t = (self.x << 4) | self.p;
+                bus.write(self.r[2], self.t);
+                self.x = self.p;
+                self.r[2] = self.r[2].wrapping_sub(1);
+            }
+            0xA => self.q = false,
+            0xB => self.q = true,
+            0xC => {
+                let m = self.immediate(bus);
+                self.add(m, self.d, self.df);
+            }
+            0xD => {
+                let m = self.immediate(bus);
+                self.subtract(m, self.d, !self.df);
+            }
+            // SHLC
+            0xE => {
+                let carry = self.d & 0x80 != 0;
+                self.d = (self.d << 1) | self.df as u8;
+                self.df = carry;
+            }
+            _ => {
+                let m = self.immediate(bus);
+                self.subtract(self.d, m, !self.df);
+            }
+        }
+    }
+
+    /// Long branches (bit 2 clear) and long skips (bit 2 set).
+    fn op_cn<B: Bus + ?Sized>(&mut self, opcode: u8, bus: &mut B) {
+        let p = self.p as usize;
+        if opcode & 0x4 == 0 {
+            if self.condition(opcode, bus) {
+                let high = bus.read(self.r[p]) as u16;
+                let low = bus.read(self.r[p].wrapping_add(1)) as u16;
+                self.r[p] = (high << 8) | low;
+            } else {
+                self.r[p] = self.r[p].wrapping_add(2);
+            }
+            return;
+        }
+
+        let skip = match opcode & 0xF {
+            // NOP
+            0x4 => false,
+            0x5 => !self.q,
+            0x6 => self.d != 0,
+            0x7 => !self.df,
+            0x8 => true,
+            0xC => self.ie,
+            0xD => self.q,
+            0xE => self.d == 0,
+            _ => self.df,
+        };
+        if skip {
+            self.r[p] = self.r[p].wrapping_add(2);
+        }
+    }
+
+    /// Logic and arithmetic on memory at R(X) (`F0`-`F7`) or immediate (`F8`-`FF`).
+    fn op_fn<B: Bus + ?Sized>(&mut self, n: usize, bus: &mut B) {
+        let m = if n < 0x8 {
+            bus.read(self.rx())
+        } else if n == 0xE {
+            0
+        } else {
+            self.immediate(bus)
+        };
+
+        match n & 0x7 {
+            0x0 => self.d = m,
+            0x1 => self.d |= m,
+            0x2 => self.d &= m,
+            0x3 => self.d ^= m,
+            0x4 => self.add(m, self.d, false),
+            0x5 => self.subtract(m, self.d, false),
+            // SHR and SHL take no operand.
+            0x6 if n == 0x6 => {
+                self.df = self.d & 1 != 0;
+                self.d >>= 1;
+            }
+            0x6 => {
+                self.df = self.d & 0x80 != 0;
+                self.d <<= 1;
+            }
+            _ => self.subtract(self.d, m, false),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs `steps` instructions of `program`, loaded at 0 with R0 as the
+    /// program counter.
+    fn run(program: &[u8], steps: usize) -> (Cdp1802, Vec<u8>) {
+        let mut memory = vec![0; 256];
+        memory[..program.len()].copy_from_slice(program);
+        let mut cpu = Cdp1802::new();
+        for _ in 0..steps {
+            cpu.step(memory.as_mut_slice());
+        }
+        (cpu, memory)
+    }
+
+    #[test]
+    fn short_branches_replace_the_low_byte() {
+        // BR 10
+        let (cpu, _) = run(&[0x30, 0x10], 1);
+        assert_eq!(cpu.r[0], 0x10);
+
+        // BZ 10 with D = 0 branches, BNZ 10 doesn't.
+        let (cpu, _) = run(&[0x32, 0x10], 1);
+        assert_eq!(cpu.r[0], 0x10);
+        let (cpu, _) = run(&[0x3A, 0x10], 1);
+        assert_eq!(cpu.r[0], 0x02);
+    }
+
+    #[test]
+    fn long_branches_and_skips() {
+        // LBR 0080
+        let (cpu, _) = run(&[0xC0, 0x00, 0x80], 1);
+        assert_eq!(cpu.r[0], 0x80);
+        assert_eq!(cpu.cycles, LONG_CYCLES as u64);
+
+        // LBNZ 0080 with D = 0 falls through.
+        let (cpu, _) = run(&[0xCA, 0x00, 0x80], 1);
+        assert_eq!(cpu.r[0], 0x03);
+
+        // LSKP skips the next two bytes.
+        let (cpu, _) = run(&[0xC8], 1);
+        assert_eq!(cpu.r[0], 0x03);
+    }
+
+    #[test]
+    fn arithmetic_on_d() {
+        // LDI F0, ADI 20: carries out.
+        let (cpu, _) = run(&[0xF8, 0xF0, 0xFC, 0x20], 2);
+        assert_eq!((cpu.d, cpu.df), (0x10, true));
+
+        // LDI 10, SMI 20: borrows, which clears DF.
+        let (cpu, _) = run(&[0xF8, 0x10, 0xFF, 0x20], 2);
+        assert_eq!((cpu.d, cpu.df), (0xF0, false));
+
+        // LDI 81, SHR: the low bit goes to DF.
+        let (cpu, _) = run(&[0xF8, 0x81, 0xF6], 2);
+        assert_eq!((cpu.d, cpu.df), (0x40, true));
+
+        // LDI FF, ADI 01, ADCI 00: adds the carry.
+        let (cpu, _) = run(&[0xF8, 0xFF, 0xFC, 0x01, 0x7C, 0x00], 3);
+        assert_eq!((cpu.d, cpu.df), (0x01, false));
+    }
+
+    #[test]
+    fn loads_and_stores_through_registers() {
+        // LDI 80, PLO R1, LDI 42, STR R1, LDN R1
+        let (cpu, memory) = run(&[0xF8, 0x80, 0xA1, 0xF8, 0x42, 0x51, 0x01], 5);
+        assert_eq!(cpu.r[1], 0x80);
+        assert_eq!(memory[0x80], 0x42);
+        assert_eq!(cpu.d, 0x42);
+    }
+
+    #[test]
+    fn sep_and_sex_select_the_registers() {
+        // SEX R7, SEP R5
+        let (cpu, _) = run(&[0xE7, 0xD5], 2);
+        assert_eq!(cpu.x, 7);
+        assert_eq!(cpu.p, 5);
+    }
+
+    #[test]
+    fn sep_r4_returns_to_the_interpreter() {
+        // The interpreter calls machine code with P = 3 and waits for P = 4.
+        let mut memory = vec![0; 256];
+        memory[0x40..0x43].copy_from_slice(&[0xF8, 0x07, 0xD4]);
+        let mut cpu = Cdp1802::new();
+        cpu.p = 3;
+        cpu.r[3] = 0x40;
+        cpu.r[4] = 0x10;
+        while cpu.p != 4 {
+            cpu.step(memory.as_mut_slice());
+        }
+        assert_eq!(cpu.d, 0x07);
+        assert_eq!(cpu.r[3], 0x43);
+        assert_eq!(cpu.r[cpu.p as usize], 0x10);
+    }
+}
@@ -0,0 +1,163 @@
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use super::cpu::Cpu;
+
+/// Where cheat lists are saved, one file per ROM.
+pub const CHEAT_DIR: &str = "cheats";
+
+/// FNV-1a hash of a ROM, which names its cheat list so it follows the ROM
+/// whatever the file is called.
+pub fn rom_hash(rom: &[u8]) -> String {
+    let hash = rom.iter().fold(0xCBF2_9CE4_8422_2325u64, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0000_0100_0000_01B3)
+    });
+    format!("{:016x}", hash)
+}
+
+/// An address kept at a fixed value, e.g. the number of lives. Addresses
+/// are wider than 16 bits to reach all of MegaChip's memory.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cheat {
+    pub address: u32,
+    pub value: u8,
+    pub name: String,
+}
+
+impl fmt::Display for Cheat {
+    /// `ADDR VALUE name`, in hex, as stored in cheat files.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:03X} {:02X} {}", self.address, self.value, self.name)
+    }
+}
+
+/// The cheats enabled for a ROM. They are written to memory at the start of
+/// every frame.
+#[derive(Clone, Debug, Default)]
+pub struct CheatList {
+    pub cheats: Vec<Cheat>,
+}
+
+impl CheatList {
+    /// `cheats/<hash>.txt` for the given ROM.
+    pub fn path_for(rom: &[u8]) -> PathBuf {
+        Path::new(CHEAT_DIR).join(format!("{}.txt", rom_hash(rom)))
+    }
+
+    /// Reads a cheat file: one `ADDR VALUE [name]` line per cheat, in hex.
+    /// Empty lines and lines starting with `#` are ignored.
+    pub fn load(path: &Path) -> io::Result<CheatList> {
+        let mut cheats = vec![];
+        for (index, line) in fs::read_to_string(path)?.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let cheat = CheatList::parse_line(line).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{}:{}: invalid cheat '{}'", path.display(), index + 1, line),
+                )
+            })?;
+            cheats.push(cheat);
+        }
+        Ok(CheatList { cheats })
+    }
+
+    fn parse_line(line: &str) -> Option<Cheat> {
+        let mut parts = line.splitn(3, char::is_whitespace);
+        let address = u32::from_str_radix(parts.next()?, 16).ok()?;
+        let value = u8::from_str_radix(parts.next()?.trim(), 16).ok()?;
+        let name = parts.next().unwrap_or_default().trim().to_string();
+        Some(Cheat {
+            address,
+            value,
+            name,
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut content = String::from("# ADDR VALUE name\n");
+        for cheat in &self.cheats {
+            content.push_str(&format!("{}\n", cheat));
+        }
+        fs::write(path, content)
+    }
+
+    /// Freezes `address` at `value`, replacing any cheat on the same address.
+    pub fn freeze(&mut self, address: u32, value: u8, name: &str) {
+        self.unfreeze(address);
+        self.cheats.push(Cheat {
+            address,
+            value,
+            name: name.to_string(),
+        });
+    }
+
+    /// Returns `false` if there was no cheat on `address`.
+    pub fn unfreeze(&mut self, address: u32) -> bool {
+        let count = self.cheats.len();
+        self.cheats.retain(|cheat| cheat.address != address);
+        self.cheats.len() != count
+    }
+
+    pub fn apply(&self, cpu: &mut Cpu) {
+        for cheat in &self.cheats {
+            if (cheat.address as usize) < cpu.memory().len() {
+                cpu.write(cheat.address, cheat.value);
+            }
+        }
+    }
+}
+
+/// How candidates are narrowed down, comparing memory to the last snapshot.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SearchFilter {
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased,
+    Equal(u8),
+}
+
+impl SearchFilter {
+    fn matches(&self, before: u8, now: u8) -> bool {
+        match *self {
+            SearchFilter::Changed => now != before,
+            SearchFilter::Unchanged => now == before,
+            SearchFilter::Increased => now > before,
+            SearchFilter::Decreased => now < before,
+            SearchFilter::Equal(value) => now == value,
+        }
+    }
+}
+
+/// Finds the address of a value (lives, score...) by taking snapshots of
+/// memory and keeping the addresses that behaved as expected in between,
+/// e.g. "decreased" after losing a life.
+pub struct CheatSearch {
+    snapshot: Vec<u8>,
+    pub candidates: Vec<u32>,
+}
+
+impl CheatSearch {
+    /// Starts a search with every address as a candidate.
+    pub fn new(cpu: &Cpu) -> Self {
+        Self {
+            snapshot: cpu.memory().to_vec(),
+            candidates: (0..cpu.memory().len() as u32).collect(),
+        }
+    }
+
+    /// Keeps the candidates matching `filter`, then takes a new snapshot.
+    pub fn narrow(&mut self, cpu: &Cpu, filter: SearchFilter) {
+        self.candidates
+            .retain(|address| filter.matches(self.snapshot[*address as usize], cpu.read(*address)));
+        self.snapshot = cpu.memory().to_vec();
+    }
+}
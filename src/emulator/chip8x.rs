@@ -0,0 +1,102 @@
+//! The VP-590 color board used by CHIP-8X. The screen stays 1-bit: the
+//! board gives every 8 pixel wide zone of a row its own foreground color,
+//! over a background shared by the whole screen.
+
+use std::ops::Range;
+
+use super::palette::Palette;
+
+/// Colors a zone can be set to with `BXYN`.
+pub const FOREGROUND_COLORS: [[u8; 3]; 8] = [
+    [0x00, 0x00, 0x00], // Black
+    [0xFF, 0x00, 0x00], // Red
+    [0x00, 0x00, 0xFF], // Blue
+    [0xFF, 0x00, 0xFF], // Violet
+    [0x00, 0xFF, 0x00], // Green
+    [0xFF, 0xFF, 0x00], // Yellow
+    [0x00, 0xFF, 0xFF], // Aqua
+    [0xFF, 0xFF, 0xFF], // White
+];
+
+/// Background colors, in the order `02A0` cycles through them.
+pub const BACKGROUND_COLORS: [[u8; 3]; 4] = [
+    [0x00, 0x00, 0x80], // Blue
+    [0x00, 0x00, 0x00], // Black
+    [0x00, 0x80, 0x00], // Green
+    [0x80, 0x00, 0x00], // Red
+];
+
+/// Zones are 8 pixels wide and 1 row high.
+pub const ZONE_WIDTH: usize = 8;
+/// `BXY0` counts rows in groups of 4.
+const BAND_HEIGHT: usize = 4;
+/// After a reset the screen is red on blue.
+const DEFAULT_FOREGROUND: u8 = 1;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColorZones {
+    columns: usize,
+    rows: usize,
+    /// Foreground color index of every zone, row by row.
+    zones: Vec<u8>,
+    /// Index in [`BACKGROUND_COLORS`].
+    pub background: usize,
+}
+
+impl ColorZones {
+    pub fn new(width: usize, height: usize) -> Self {
+        let columns = width / ZONE_WIDTH;
+        Self {
+            columns,
+            rows: height,
+            zones: vec![DEFAULT_FOREGROUND; columns * height],
+            background: 0,
+        }
+    }
+
+    /// ## 0x02A0
+    /// Moves to the next background color.
+    pub fn cycle_background(&mut self) {
+        self.background = (self.background + 1) % BACKGROUND_COLORS.len();
+    }
+
+    /// ## 0xBXY0
+    /// Colors bands of 4 rows. The low nibble of `horizontal` is the first
+    /// zone and its high nibble how many more; `vertical` does the same for
+    /// bands.
+    pub fn fill_bands(&mut self, horizontal: u8, vertical: u8, color: u8) {
+        let span = |value: u8| {
+            let start = (value & 0xF) as usize;
+            start..start + (value >> 4) as usize + 1
+        };
+        let bands = span(vertical);
+        let rows = bands.start * BAND_HEIGHT..bands.end * BAND_HEIGHT;
+        self.fill(span(horizontal), rows, color);
+    }
+
+    /// ## 0xBXYN
+    /// Colors the zone holding pixel (`x`, `y`) and the `rows` - 1 below it.
+    pub fn fill_rows(&mut self, x: u8, y: u8, rows: u8, color: u8) {
+        let column = (x as usize % (self.columns * ZONE_WIDTH)) / ZONE_WIDTH;
+        let top = y as usize % self.rows;
+        self.fill(column..column + 1, top..top + rows as usize, color);
+    }
+
+    /// Zones past the edges of the screen are left alone.
+    fn fill(&mut self, columns: Range<usize>, rows: Range<usize>, color: u8) {
+        for row in rows.start..rows.end.min(self.rows) {
+            for column in columns.start..columns.end.min(self.columns) {
+                self.zones[row * self.columns + column] = color & 0x7;
+            }
+        }
+    }
+
+    /// The colors of pixel (`x`, `y`), to present it with.
+    pub fn palette_at(&self, x: usize, y: usize) -> Palette {
+        let zone = self.zones[(y % self.rows) * self.columns + (x / ZONE_WIDTH) % self.columns];
+        Palette {
+            foreground: FOREGROUND_COLORS[zone as usize],
+            background: BACKGROUND_COLORS[self.background],
+        }
+    }
+}
@@ -0,0 +1,49 @@
+use std::time::{Duration, Instant};
+
+/// Timers and the screen both run at 60 Hz.
+pub const FRAME_DURATION: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+/// If the host falls behind (e.g. the window is being dragged) we drop frames
+/// instead of fast-forwarding through all of them.
+const MAX_FRAMES_BEHIND: u32 = 4;
+
+/// Keeps the emulation running at 60 frames per second, whatever the frontend.
+pub struct FrameClock {
+    next_frame: Instant,
+}
+
+impl FrameClock {
+    pub fn new() -> Self {
+        Self {
+            next_frame: Instant::now(),
+        }
+    }
+
+    /// When the next frame is due.
+    pub fn next_frame(&self) -> Instant {
+        self.next_frame
+    }
+
+    /// Returns how many frames should be run since the last call.
+    pub fn frames_due(&mut self) -> u32 {
+        let now = Instant::now();
+        let mut frames = 0;
+        while self.next_frame <= now {
+            self.next_frame += FRAME_DURATION;
+            frames += 1;
+        }
+
+        if frames > MAX_FRAMES_BEHIND {
+            self.next_frame = now + FRAME_DURATION;
+            frames = MAX_FRAMES_BEHIND;
+        }
+
+        frames
+    }
+}
+
+impl Default for FrameClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
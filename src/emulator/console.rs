@@ -0,0 +1,190 @@
+use std::{
+    io::{self, BufRead},
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread,
+};
+
+use super::{
+    cheats::{CheatList, CheatSearch, SearchFilter},
+    machine::Machine,
+};
+
+/// Candidates printed by `list`, searches usually need a few more steps
+/// before they are worth reading.
+const MAX_LISTED_CANDIDATES: usize = 32;
+
+const HELP: &str = "\
+Memory:
+  peek ADDR [LEN]          show LEN bytes (default 16) from ADDR
+  poke ADDR VALUE          write VALUE at ADDR
+Search:
+  search                   start a new search, every address is a candidate
+  changed | unchanged      keep the candidates that changed or not since the last step
+  increased | decreased    keep the candidates that went up or down
+  equal VALUE              keep the candidates holding VALUE
+  list                     show the candidates and their current value
+Cheats:
+  freeze ADDR VALUE [name] keep ADDR at VALUE every frame
+  unfreeze ADDR            stop freezing ADDR
+  cheats                   list the frozen addresses
+  save | load              save or load the cheats of this ROM
+Addresses and values are in hex.";
+
+/// Reads commands from the terminal the emulator was started from, to edit
+/// memory and look for cheats while the game runs.
+pub struct CheatConsole {
+    commands: Receiver<String>,
+    search: Option<CheatSearch>,
+}
+
+impl CheatConsole {
+    /// Starts reading stdin in the background.
+    pub fn start() -> Self {
+        let (sender, commands) = mpsc::channel();
+        thread::spawn(move || {
+            for line in io::stdin().lock().lines() {
+                let Ok(line) = line else { break };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        println!("Cheat console ready, type 'help' for the commands.");
+        Self {
+            commands,
+            search: None,
+        }
+    }
+
+    /// Runs the commands typed since the last call.
+    pub fn poll(&mut self, machine: &mut Machine) {
+        loop {
+            match self.commands.try_recv() {
+                Ok(line) => {
+                    if let Err(message) = self.run(machine, line.trim()) {
+                        println!("{}", message);
+                    }
+                }
+                Err(TryRecvError::Empty | TryRecvError::Disconnected) => return,
+            }
+        }
+    }
+
+    fn run(&mut self, machine: &mut Machine, line: &str) -> Result<(), String> {
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else {
+            return Ok(());
+        };
+        let arguments: Vec<&str> = words.collect();
+        let cpu = &mut machine.cpu;
+        let memory_size = cpu.memory().len();
+
+        match command {
+            "help" => println!("{}", HELP),
+            "peek" => {
+                let address = CheatConsole::address(arguments.first(), memory_size)?;
+                let len = match arguments.get(1) {
+                    Some(len) => CheatConsole::hex(Some(len))? as usize,
+                    None => 16,
+                };
+                let end = (address as usize + len).min(cpu.memory().len());
+                for (row, bytes) in cpu.memory()[address as usize..end].chunks(16).enumerate() {
+                    let bytes: Vec<String> =
+                        bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+                    println!("{:03X}: {}", address as usize + row * 16, bytes.join(" "));
+                }
+            }
+            "poke" => {
+                let address = CheatConsole::address(arguments.first(), memory_size)?;
+                let value = CheatConsole::value(arguments.get(1))?;
+                cpu.write(address, value);
+            }
+            "search" => {
+                let search = CheatSearch::new(cpu);
+                println!("{} candidates", search.candidates.len());
+                self.search = Some(search);
+            }
+            "changed" | "unchanged" | "increased" | "decreased" | "equal" => {
+                let filter = match command {
+                    "changed" => SearchFilter::Changed,
+                    "unchanged" => SearchFilter::Unchanged,
+                    "increased" => SearchFilter::Increased,
+                    "decreased" => SearchFilter::Decreased,
+                    _ => SearchFilter::Equal(CheatConsole::value(arguments.first())?),
+                };
+                let search = self.search.get_or_insert_with(|| CheatSearch::new(cpu));
+                search.narrow(cpu, filter);
+                println!("{} candidates", search.candidates.len());
+            }
+            "list" => {
+                let search = self
+                    .search
+                    .as_ref()
+                    .ok_or("No search running, use 'search'.")?;
+                for address in search.candidates.iter().take(MAX_LISTED_CANDIDATES) {
+                    println!("{:03X}: {:02X}", address, cpu.read(*address));
+                }
+                if search.candidates.len() > MAX_LISTED_CANDIDATES {
+                    println!(
+                        "... {} more",
+                        search.candidates.len() - MAX_LISTED_CANDIDATES
+                    );
+                }
+            }
+            "freeze" => {
+                let address = CheatConsole::address(arguments.first(), memory_size)?;
+                let value = CheatConsole::value(arguments.get(1))?;
+                machine
+                    .cheats
+                    .freeze(address, value, &arguments[2..].join(" "));
+            }
+            "unfreeze" => {
+                let address = CheatConsole::address(arguments.first(), memory_size)?;
+                if !machine.cheats.unfreeze(address) {
+                    return Err(format!("{:03X} is not frozen.", address));
+                }
+            }
+            "cheats" => {
+                for cheat in &machine.cheats.cheats {
+                    println!("{}", cheat);
+                }
+            }
+            "save" => {
+                let path = CheatList::path_for(machine.rom());
+                machine
+                    .cheats
+                    .save(&path)
+                    .map_err(|err| format!("Cannot save cheats: {}", err))?;
+                println!("Saved cheats to '{}'", path.display());
+            }
+            "load" => {
+                let path = CheatList::path_for(machine.rom());
+                machine.cheats =
+                    CheatList::load(&path).map_err(|err| format!("Cannot load cheats: {}", err))?;
+                println!("Loaded {} cheats", machine.cheats.cheats.len());
+            }
+            _ => return Err(format!("Unknown command '{}', type 'help'.", command)),
+        }
+        Ok(())
+    }
+
+    fn hex(argument: Option<&&str>) -> Result<u32, String> {
+        let argument = argument.ok_or("Missing argument, type 'help'.")?;
+        let digits = argument.trim_start_matches("0x");
+        u32::from_str_radix(digits, 16).map_err(|_| format!("Invalid hex number '{}'.", argument))
+    }
+
+    fn address(argument: Option<&&str>, memory_size: usize) -> Result<u32, String> {
+        let address = CheatConsole::hex(argument)?;
+        if address as usize >= memory_size {
+            return Err(format!("Address {:X} is out of memory.", address));
+        }
+        Ok(address)
+    }
+
+    fn value(argument: Option<&&str>) -> Result<u8, String> {
+        let value = CheatConsole::hex(argument)?;
+        u8::try_from(value).map_err(|_| format!("Value {:X} does not fit in a byte.", value))
+    }
+}
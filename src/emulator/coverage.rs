@@ -0,0 +1,211 @@
+use std::{io, io::Write, ops::Range};
+
+use super::disasm::{self, Instruction};
+
+pub const EXECUTED: u8 = 0b001;
+pub const READ: u8 = 0b010;
+pub const WRITTEN: u8 = 0b100;
+
+const MAP_BYTES_PER_ROW: usize = 32;
+/// Data bytes per `db` line of the listing.
+const LISTING_BYTES_PER_LINE: usize = 8;
+
+/// Remembers how every byte of memory was used: executed as an instruction,
+/// read as data (sprites, FX65) or written (FX33, FX55).
+#[derive(Clone, Debug)]
+pub struct Coverage {
+    flags: Vec<u8>,
+}
+
+impl Coverage {
+    pub fn new(memory_size: usize) -> Self {
+        Self {
+            flags: vec![0; memory_size],
+        }
+    }
+
+    /// Marks both bytes of the instruction at `address`.
+    pub fn mark_executed(&mut self, address: u16) {
+        self.mark(address as usize, EXECUTED);
+        self.mark(address.wrapping_add(1) as usize, EXECUTED);
+    }
+
+    /// Data can be anywhere in MegaChip's 24-bit address space.
+    pub fn mark_read(&mut self, address: u32) {
+        self.mark(address as usize, READ);
+    }
+
+    pub fn mark_written(&mut self, address: u32) {
+        self.mark(address as usize, WRITTEN);
+    }
+
+    fn mark(&mut self, address: usize, flag: u8) {
+        if let Some(flags) = self.flags.get_mut(address) {
+            *flags |= flag;
+        }
+    }
+
+    /// The [`EXECUTED`], [`READ`] and [`WRITTEN`] flags of `address`.
+    pub fn flags(&self, address: usize) -> u8 {
+        self.flags.get(address).copied().unwrap_or(0)
+    }
+
+    /// Writes a summary, a map with one character per byte and a listing
+    /// where executed bytes are disassembled and the rest is shown as data.
+    /// Covers `program`, extended to the last byte used after it.
+    pub fn write_map(
+        &self,
+        out: &mut dyn Write,
+        memory: &[u8],
+        program: Range<usize>,
+    ) -> io::Result<()> {
+        let last_used = (program.start..self.flags.len())
+            .rev()
+            .find(|address| self.flags[*address] != 0);
+        let range = program.start..program.end.max(last_used.map_or(0, |address| address + 1));
+        let len = range.len();
+
+        let count = |flag: u8| {
+            range
+                .clone()
+                .filter(|address| self.flags(*address) & flag != 0)
+                .count()
+        };
+        let untouched = range
+            .clone()
+            .filter(|address| self.flags(*address) == 0)
+            .count();
+        let percent = |count: usize| 100.0 * count as f64 / len.max(1) as f64;
+
+        writeln!(
+            out,
+            "Coverage of {:04X}-{:04X} ({} bytes)",
+            range.start,
+            range.end.saturating_sub(1),
+            len
+        )?;
+        writeln!(
+            out,
+            "  executed:  {:5} bytes {:5.1}%",
+            count(EXECUTED),
+            percent(count(EXECUTED))
+        )?;
+        writeln!(
+            out,
+            "  read:      {:5} bytes {:5.1}%",
+            count(READ),
+            percent(count(READ))
+        )?;
+        writeln!(
+            out,
+            "  written:   {:5} bytes {:5.1}%",
+            count(WRITTEN),
+            percent(count(WRITTEN))
+        )?;
+        writeln!(
+            out,
+            "  untouched: {:5} bytes {:5.1}%",
+            untouched,
+            percent(untouched)
+        )?;
+
+        writeln!(
+            out,
+            "\nMap (x executed, r read, w written, * several, . untouched):"
+        )?;
+        for row_start in range.clone().step_by(MAP_BYTES_PER_ROW) {
+            let row: String = (row_start..(row_start + MAP_BYTES_PER_ROW).min(range.end))
+                .map(|address| Coverage::symbol(self.flags(address)))
+                .collect();
+            writeln!(out, "{:04X}  {}", row_start, row)?;
+        }
+
+        writeln!(out, "\nListing:")?;
+        let mut address = range.start;
+        while address < range.end {
+            let flags = self.flags(address);
+            if flags & EXECUTED != 0 {
+                writeln!(
+                    out,
+                    "{:04X}  {}  {:04X}  {}",
+                    address,
+                    Coverage::symbol(flags),
+                    disasm::opcode_at(memory, address as u16),
+                    Instruction::at(memory, address as u16)
+                )?;
+                address += 2;
+                continue;
+            }
+
+            // Groups data bytes used the same way.
+            let end = (address..range.end)
+                .take(LISTING_BYTES_PER_LINE)
+                .take_while(|next| self.flags(*next) == flags)
+                .last()
+                .map_or(address + 1, |last| last + 1);
+            let bytes: Vec<String> = memory
+                .get(address..end.min(memory.len()))
+                .unwrap_or_default()
+                .iter()
+                .map(|byte| format!("{:02X}", byte))
+                .collect();
+            writeln!(
+                out,
+                "{:04X}  {}  db {}",
+                address,
+                Coverage::symbol(flags),
+                bytes.join(" ")
+            )?;
+            address = end;
+        }
+        Ok(())
+    }
+
+    fn symbol(flags: u8) -> char {
+        match flags {
+            0 => '.',
+            EXECUTED => 'x',
+            READ => 'r',
+            WRITTEN => 'w',
+            _ => '*',
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn marks_both_bytes_of_executed_instructions() {
+        let mut coverage = Coverage::new(16);
+        coverage.mark_executed(2);
+        coverage.mark_read(6);
+        coverage.mark_written(6);
+        assert_eq!(coverage.flags(2), EXECUTED);
+        assert_eq!(coverage.flags(3), EXECUTED);
+        assert_eq!(coverage.flags(6), READ | WRITTEN);
+        assert_eq!(coverage.flags(4), 0);
+    }
+
+    #[test]
+    fn ignores_addresses_past_memory() {
+        let mut coverage = Coverage::new(16);
+        coverage.mark_executed(15);
+        coverage.mark_read(0x10000);
+        assert_eq!(coverage.flags(15), EXECUTED);
+        assert_eq!(coverage.flags(0x10000), 0);
+    }
+
+    #[test]
+    fn map_of_a_program_past_the_end_of_memory() {
+        let memory = [0x00, 0xE0, 0x12, 0x00, 0xAA, 0xBB];
+        let mut coverage = Coverage::new(memory.len());
+        coverage.mark_executed(0);
+        let mut out = vec![];
+        coverage.write_map(&mut out, &memory, 0..10).unwrap();
+        let map = String::from_utf8(out).unwrap();
+        assert!(map.contains("0000  x  00E0  CLS"));
+        assert!(map.contains("0002  .  db 12 00 AA BB"));
+    }
+}
@@ -1,37 +1,60 @@
-use log::{debug, info};
+use log::{debug, info, warn};
 use rand::{prelude::ThreadRng, rng, Rng};
-use std::{collections::HashSet, fmt};
-
-/// Chip-8 has 16 sprites of 5 bytes (16 * 5 = 80)
-///
-/// They represent the hex digits of 0..F
-const BUILT_IN_FONTSET: [u8; 80] = [
-    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
-    0x20, 0x60, 0x20, 0x20, 0x70, // 1
-    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
-    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
-    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
-    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
-    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
-    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
-    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
-    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
-    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
-    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
-    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
-    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
-    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
-    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
-];
-const FONTSET_START_ADDRESS: u16 = 0x0;
-
+use std::fmt;
+
+use super::{
+    bus::Bus,
+    cdp1802::Cdp1802,
+    chip8x::ColorZones,
+    coverage::{self, Coverage},
+    error::CpuError,
+    fontset::{FontSet, FONT_SIZE},
+    megachip::{self, MegaChip},
+    memory_policy::MemoryPolicy,
+    platform::Platform,
+    profiler::Profiler,
+    quirks::Quirks,
+};
+
+/// Where the font sprites go unless configured otherwise, see [`Cpu::load_font`].
+pub const FONTSET_START_ADDRESS: u16 = 0x0;
+
+/// Where programs are loaded and the screen size of the original
+/// interpreter. Other platforms change them, see [`Platform`].
 pub const PROGRAM_START: u16 = 0x200;
 pub const SCREEN_WIDTH: usize = 64;
 pub const SCREEN_HEIGHT: usize = 32;
 
+/// Where a HIRES program jumps from `0200` to switch to 64x64, and where
+/// the jump actually lands, past the code patching the interpreter.
+const HIRES_TRAMPOLINE: u16 = 0x260;
+const HIRES_PROGRAM_START: u16 = 0x2C0;
+
+/// Where the VIP interpreter keeps V0-VF, the 1802 stack and the display
+/// buffer, which machine code called by `0NNN` expects to find.
+pub const VIP_REGISTERS_ADDRESS: u16 = 0xEF0;
+pub const VIP_STACK_ADDRESS: u16 = 0xECF;
+/// Where the VIP interpreter keeps the CHIP-8 return addresses, growing up,
+/// and how many fit below the 1802 stack at [`VIP_STACK_ADDRESS`]: the last
+/// one ends at `ECD`.
+pub const VIP_CALL_STACK_ADDRESS: u16 = 0xEA0;
+pub const VIP_CALL_STACK_DEPTH: usize = 23;
+pub const VIP_DISPLAY_ADDRESS: u16 = 0xF00;
+/// Machine code that runs longer than this (about 4 seconds) is assumed to be stuck.
+const MACHINE_CODE_CYCLE_LIMIT: u64 = 1_000_000;
+
 pub struct Cpu {
-    /// CHIP-8 has 4K memory
-    pub memory: [u8; 4096],
+    /// Memory and whatever else is mapped in, plain RAM by default.
+    /// CHIP-8 has 4K memory, MegaChip 16M (see [`Platform::memory_size`]).
+    pub bus: Box<dyn Bus>,
+    /// Where the ROM is loaded and runs from.
+    pub program_start: u16,
+    /// Where the digit sprites of `FX29` are.
+    pub font_address: u16,
+    /// Which memory accesses are violations, see [`MemoryPolicy`].
+    pub memory_policy: MemoryPolicy,
+    /// The last violation, when they don't stop the emulation.
+    pub last_violation: Option<CpuError>,
 
     /// Opcodes are two bytes
     pub opcode: u16,
@@ -40,14 +63,23 @@ pub struct Cpu {
     /// From V0 to VF
     pub v: [u8; 16],
 
-    /// Index register 'I'
-    pub i: u16,
+    /// Index register 'I', 24 bits wide on MegaChip.
+    pub i: u32,
 
     /// Program Counter (PC)
     pub pc: u16,
 
-    /// Screen of 64x32, pixels have only one color.
-    pub screen: [u8; SCREEN_WIDTH * SCREEN_HEIGHT],
+    /// Screen of `width` x `height`, pixels have only one color.
+    pub screen: Vec<u8>,
+    pub width: usize,
+    pub height: usize,
+
+    /// Which variant of the interpreter is emulated, see [`Cpu::set_platform`].
+    pub platform: Platform,
+    /// Foreground and background colors on CHIP-8X.
+    pub colors: Option<ColorZones>,
+    /// The color mode of MegaChip.
+    pub megachip: Option<MegaChip>,
 
     /// These two timers work the same way.
     /// Counted at 60 Hz. When set above zero, they count down to zero.
@@ -56,67 +88,242 @@ pub struct Cpu {
 
     /// Call stack
     pub stack: Vec<u16>,
+    /// How many return addresses fit, see [`Platform::stack_depth`].
+    pub stack_depth: usize,
+    /// Also keeps the return addresses in memory at
+    /// [`VIP_CALL_STACK_ADDRESS`], big endian, for ROMs that read or patch
+    /// them. `00EE` then returns to what memory holds. The depth is then at
+    /// most [`VIP_CALL_STACK_DEPTH`].
+    pub stack_in_memory: bool,
 
     /// Keyboard with 16 keys.
     /// `true` = pressed
     pub keys: [bool; 16],
+    /// The second keypad of CHIP-8X, read by `EXF2` and `EXF5`.
+    pub keys2: [bool; 16],
 
     pub draw_flag: bool,
 
+    /// Set at every frame boundary, and used up by the first sprite drawn
+    /// after it with [`Quirks::display_wait`].
+    vblank: bool,
+    /// `DXYN` is stalled until the next frame, see [`Quirks::display_wait`].
+    pub waiting_for_vblank: bool,
+
+    /// Which interpreter behaviour to follow where they disagree.
+    pub quirks: Quirks,
+
     rng: ThreadRng,
 
     // Used to get the correct bahaviour for FX0A.
     pressed_key_index: Option<usize>,
 
-    debug: HashSet<String>,
+    /// Counts the instructions run, see [`Profiler`].
+    pub profiler: Profiler,
+    /// How every byte of memory was used, see [`Coverage`].
+    pub coverage: Coverage,
 }
 
 impl Cpu {
     pub fn new() -> Self {
         let mut cpu = Cpu {
-            memory: [0; 4096],
+            bus: Box::new(vec![0; 4096]),
+            program_start: PROGRAM_START,
+            font_address: FONTSET_START_ADDRESS,
+            memory_policy: MemoryPolicy::default(),
+            last_violation: None,
             v: [0; 16],
             i: 0,
             pc: PROGRAM_START,
 
-            screen: [0; SCREEN_WIDTH * SCREEN_HEIGHT],
+            screen: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT],
+            width: SCREEN_WIDTH,
+            height: SCREEN_HEIGHT,
+            platform: Platform::Chip8,
+            colors: None,
+            megachip: None,
 
             delay_timer: 0,
             sound_timer: 0,
 
             stack: vec![],
+            stack_depth: Platform::Chip8.stack_depth(),
+            stack_in_memory: false,
 
             keys: [false; 16],
+            keys2: [false; 16],
             opcode: 0,
 
             draw_flag: false,
+            vblank: false,
+            waiting_for_vblank: false,
+            quirks: Quirks::default(),
             rng: rng(),
             pressed_key_index: None,
-            debug: HashSet::new(),
+            profiler: Profiler::new(4096),
+            coverage: Coverage::new(4096),
         };
 
         // Place the font sprites int the interpreter area of the ram
-        let start_of_fontset = FONTSET_START_ADDRESS as usize;
-        let end_of_fontset = start_of_fontset + BUILT_IN_FONTSET.len();
-        for i in start_of_fontset..end_of_fontset {
-            cpu.memory[i] = BUILT_IN_FONTSET[i];
-        }
+        cpu.load_font(FontSet::default(), FONTSET_START_ADDRESS);
 
         cpu
     }
 
+    /// Switches to the screen size, program start and extensions of
+    /// `platform`. Call it before loading the ROM.
+    pub fn set_platform(&mut self, platform: Platform) {
+        self.platform = platform;
+        self.set_program_start(platform.program_start());
+        self.stack_depth = platform.stack_depth();
+        let (width, height) = platform.resolution();
+        self.set_resolution(width, height);
+        self.colors = (platform == Platform::Chip8X).then(|| ColorZones::new(width, height));
+        self.megachip = (platform == Platform::MegaChip).then(MegaChip::new);
+        let size = platform.memory_size();
+        if self.memory().len() != size {
+            let mut memory = self.memory().to_vec();
+            memory.resize(size, 0);
+            self.bus = Box::new(memory);
+            self.profiler = Profiler::new(size);
+            self.coverage = Coverage::new(size);
+        }
+    }
+
+    /// Attaches `bus`, with the memory so far copied into it. Call it after
+    /// [`Cpu::set_platform`], which goes back to plain RAM when it resizes memory.
+    pub fn set_bus(&mut self, mut bus: Box<dyn Bus>) {
+        let len = bus.memory().len().min(self.memory().len());
+        bus.memory_mut()[..len].copy_from_slice(&self.memory()[..len]);
+        self.bus = bus;
+    }
+
+    /// The memory, without going through the bus.
+    pub fn memory(&self) -> &[u8] {
+        self.bus.memory()
+    }
+
+    pub fn memory_mut(&mut self) -> &mut [u8] {
+        self.bus.memory_mut()
+    }
+
+    /// Resizes the screen, which clears it.
+    fn set_resolution(&mut self, width: usize, height: usize) {
+        self.width = width;
+        self.height = height;
+        self.screen = vec![0; width * height];
+        self.draw_flag = true;
+    }
+
+    /// Moves the program start, e.g. to `0600` for the ETI-660. Call it
+    /// after [`Cpu::set_platform`] and before loading the ROM.
+    pub fn set_program_start(&mut self, address: u16) {
+        self.program_start = address;
+        self.pc = address;
+    }
+
+    /// Replaces the digit sprites with `font`, placed at `address`. The
+    /// previous ones are erased, for ROMs that read the font area.
+    pub fn load_font(&mut self, font: FontSet, address: u16) {
+        let old = self.font_address as usize;
+        self.memory_mut()[old..old + FONT_SIZE].fill(0);
+        let start = address as usize;
+        self.memory_mut()[start..start + FONT_SIZE].copy_from_slice(font.glyphs());
+        self.font_address = address;
+    }
+
+    /// Copies `rom` at the program start, as much of it as memory holds.
     pub fn load_rom(&mut self, rom: &[u8]) {
-        for i in 0..rom.len() {
-            self.write(PROGRAM_START + (i as u16), rom[i]);
+        let start = self.program_start as usize;
+        let end = (start + rom.len()).min(self.memory().len());
+        self.memory_mut()[start..end].copy_from_slice(&rom[..end - start]);
+    }
+
+    /// Peeks at memory, without the side effects of a read on the bus.
+    pub fn read(&self, address: u32) -> u8 {
+        self.memory()[address as usize]
+    }
+
+    pub fn write(&mut self, address: u32, value: u8) {
+        self.bus.write(address, value);
+    }
+
+    /// Reads data for an instruction, as opposed to fetching one.
+    /// Data is addressed through I, which is wider than PC on MegaChip.
+    /// Reads 0 past the end of memory if that's a reported violation.
+    fn read_data(&mut self, address: u32) -> Result<u8, CpuError> {
+        let Some(address) = self.check_range(address)? else {
+            return Ok(0);
+        };
+        self.coverage.mark_read(address as u32);
+        Ok(self.bus.read(address as u32))
+    }
+
+    /// Writes data for an instruction, as opposed to loading the program.
+    /// Writes that are reported violations are dropped.
+    fn write_data(&mut self, address: u32, value: u8) -> Result<(), CpuError> {
+        let Some(address) = self.check_range(address)? else {
+            return Ok(());
+        };
+        if self.memory_policy.protect_interpreter && address < self.program_start as usize {
+            let target = address as u16;
+            return self.violation(CpuError::ProtectedWrite {
+                address: self.pc,
+                target,
+            });
+        }
+        self.coverage.mark_written(address as u32);
+        self.bus.write(address as u32, value);
+        Ok(())
+    }
+
+    /// Fetches the opcode at PC.
+    fn fetch(&mut self) -> Result<u16, CpuError> {
+        let data = coverage::READ | coverage::WRITTEN;
+        if self.memory_policy.data_execution && self.coverage.flags(self.pc as usize) & data != 0 {
+            self.violation(CpuError::DataExecuted { address: self.pc })?;
+        }
+        let mut bytes = [0; 2];
+        for (offset, byte) in bytes.iter_mut().enumerate() {
+            if let Some(address) = self.check_range(self.pc as u32 + offset as u32)? {
+                *byte = self.bus.read(address as u32);
+            }
+        }
+        Ok(u16::from_be_bytes(bytes))
+    }
+
+    /// Wraps `address` around the end of memory, or gives `None` if that's
+    /// a reported violation, see [`MemoryPolicy::out_of_range`].
+    fn check_range(&mut self, address: u32) -> Result<Option<usize>, CpuError> {
+        let size = self.memory().len();
+        if (address as usize) < size {
+            Ok(Some(address as usize))
+        } else if !self.memory_policy.out_of_range {
+            Ok(Some(address as usize % size))
+        } else {
+            self.violation(CpuError::AddressOutOfRange {
+                address: self.pc,
+                target: address,
+            })?;
+            Ok(None)
         }
     }
 
-    pub fn read(&self, address: u16) -> u8 {
-        self.memory[address as usize]
+    /// Stops with `error` or keeps it for the debugger, see [`MemoryPolicy::stop`].
+    fn violation(&mut self, error: CpuError) -> Result<(), CpuError> {
+        if self.memory_policy.stop {
+            return Err(error);
+        }
+        warn!("{}", error);
+        self.last_violation = Some(error);
+        Ok(())
     }
 
-    pub fn write(&mut self, address: u16, value: u8) {
-        self.memory[address as usize] = value;
+    /// Called by the frame scheduler at every 60 Hz frame boundary, lets a
+    /// sprite waiting for the vertical blank be drawn.
+    pub fn start_frame(&mut self) {
+        self.vblank = true;
+        self.waiting_for_vblank = false;
     }
 
     pub fn tick_timers(&mut self) {
@@ -132,28 +339,28 @@ impl Cpu {
         }
     }
 
-    fn get_screen_index(x: u8, y: u8) -> usize {
-        ((usize::from(y) % SCREEN_HEIGHT) * SCREEN_WIDTH) + (usize::from(x) % SCREEN_WIDTH)
+    fn get_screen_index(&self, x: usize, y: usize) -> usize {
+        ((y % self.height) * self.width) + (x % self.width)
     }
 
     /// Draws on screen memory address.
     /// Returns `true` if there's pixel collision.
-    fn set_screen_pixel(&mut self, x: u8, y: u8, value: u8) -> bool {
-        let old = self.screen[Cpu::get_screen_index(x, y)];
+    fn set_screen_pixel(&mut self, x: usize, y: usize, value: u8) -> bool {
+        let index = self.get_screen_index(x, y);
+        let old = self.screen[index];
 
         if value > 0 {
-            self.screen[Cpu::get_screen_index(x, y)] ^= 0xFF;
+            self.screen[index] ^= 0xFF;
         } else {
-            self.screen[Cpu::get_screen_index(x, y)] ^= 0x0000;
+            self.screen[index] ^= 0x0000;
         }
 
-        self.screen[Cpu::get_screen_index(x, y)] != old
+        self.screen[index] != old
     }
-    
-    fn print_debug(&mut self, routine: &str) {
-        if !self.debug.contains(routine) {
-            println!("opcode {}", routine);
-            self.debug.insert(String::from(routine));
+
+    fn profile(&mut self, routine: &'static str) {
+        if self.profiler.record_class(routine) {
+            debug!("first use of opcode {}", routine);
         }
     }
 
@@ -162,26 +369,31 @@ impl Cpu {
         self.pc += 2;
     }
 
-    pub fn run_instruction(&mut self) {
+    pub fn run_instruction(&mut self) -> Result<(), CpuError> {
         // opcodes are 16-bit (must read and combine two bytes)
-        let low = self.read(self.pc) as u16;
-        let high = self.read(self.pc + 1) as u16;
-        let opcode = (low << 8) | high; // Big-Endian
+        let opcode = self.fetch()?; // Big-Endian
 
-        if high == 0 && low == 0 {
-            panic!("opcode is zero");
+        if opcode == 0 {
+            return Err(self.unknown_opcode(opcode));
         }
 
         self.draw_flag = false;
         self.opcode = opcode;
 
         debug!("opcode {:#x}", opcode);
+        self.profiler.record_execution(self.pc);
+        self.coverage.mark_executed(self.pc);
 
         match opcode & 0xF000 {
-            0x0000 => match opcode & 0x00FF {
+            0x0000 => match opcode {
+                0x0230 if self.platform == Platform::Hires => self.op_00e0(),
+                0x02A0 if self.platform == Platform::Chip8X => self.op_02a0(),
+                0x0010 | 0x0011 | 0x0100..=0x09FF if self.platform == Platform::MegaChip => {
+                    self.op_megachip(opcode)
+                }
                 0x00E0 => self.op_00e0(),
-                0x00EE => self.op_00ee(),
-                _ => println!("0x0: Ignoring unrecognized opcode {:#X}", opcode),
+                0x00EE => self.op_00ee()?,
+                _ => self.op_0nnn(opcode)?,
             },
             0x1000 => {
                 let address = opcode & 0x0FFF;
@@ -189,7 +401,7 @@ impl Cpu {
             }
             0x2000 => {
                 let address = opcode & 0x0FFF;
-                self.op_2nnn(address);
+                self.op_2nnn(address)?;
             }
             0x3000 => {
                 let x = ((opcode & 0x0F00) >> 8) as usize;
@@ -204,7 +416,11 @@ impl Cpu {
             0x5000 => {
                 let x = ((opcode & 0x0F00) >> 8) as usize;
                 let y = ((opcode & 0x00F0) >> 4) as usize;
-                self.op_5xy0(x, y);
+                if opcode & 0x000F == 0x0001 && self.platform == Platform::Chip8X {
+                    self.op_5xy1(x, y);
+                } else {
+                    self.op_5xy0(x, y);
+                }
             }
             0x6000 => {
                 let x = ((opcode & 0x0F00) >> 8) as usize;
@@ -229,7 +445,7 @@ impl Cpu {
                     0x0006 => self.op_8xy6(x, y),
                     0x0007 => self.op_8xy7(x, y),
                     0x000E => self.op_8xye(x, y),
-                    _ => panic!("0x8: Unrecognized opcode {:#X}", opcode),
+                    _ => return Err(self.unknown_opcode(opcode)),
                 }
             }
             0x9000 => {
@@ -241,6 +457,12 @@ impl Cpu {
                 let value = opcode & 0x0FFF;
                 self.op_annn(value);
             }
+            0xB000 if self.platform == Platform::Chip8X => {
+                let x = ((opcode & 0x0F00) >> 8) as usize;
+                let y = ((opcode & 0x00F0) >> 4) as usize;
+                let nibble = (opcode & 0x000F) as u8;
+                self.op_bxyn(x, y, nibble);
+            }
             0xB000 => {
                 let value = opcode & 0x0FFF;
                 self.op_bnnn(value);
@@ -254,14 +476,16 @@ impl Cpu {
                 let x = ((opcode & 0x0F00) >> 8) as usize;
                 let y = ((opcode & 0x00F0) >> 4) as usize;
                 let nibble = (opcode & 0x000F) as u8;
-                self.op_dxyn(x, y, nibble);
+                self.op_dxyn(x, y, nibble)?;
             }
             0xE000 => {
                 let x = ((opcode & 0x0F00) >> 8) as usize;
                 match opcode & 0x00FF {
                     0x009E => self.op_ex9e(x),
                     0x00A1 => self.op_exa1(x),
-                    _ => panic!("0xE: Unrecognized opcode {:#X}", opcode),
+                    0x00F2 if self.platform == Platform::Chip8X => self.op_exf2(x),
+                    0x00F5 if self.platform == Platform::Chip8X => self.op_exf5(x),
+                    _ => return Err(self.unknown_opcode(opcode)),
                 }
             }
             0xF000 => {
@@ -273,59 +497,253 @@ impl Cpu {
                     0x0018 => self.op_fx18(x),
                     0x001E => self.op_fx1e(x),
                     0x0029 => self.op_fx29(x),
-                    0x0033 => self.op_fx33(x),
-                    0x0055 => self.op_fx55(x),
-                    0x0065 => self.op_fx65(x),
-                    _ => panic!("0xF: Unrecognized opcode {:#X}", opcode),
+                    0x0033 => self.op_fx33(x)?,
+                    0x0055 => self.op_fx55(x)?,
+                    0x0065 => self.op_fx65(x)?,
+                    0x00F8 if self.platform == Platform::Chip8X => self.op_fxf8(x),
+                    0x00FB if self.platform == Platform::Chip8X => self.op_fxfb(x),
+                    _ => return Err(self.unknown_opcode(opcode)),
                 }
             }
-            _ => panic!("Unrecognized opcode {:#X}", opcode),
+            _ => return Err(self.unknown_opcode(opcode)),
+        }
+
+        Ok(())
+    }
+
+    fn unknown_opcode(&self, opcode: u16) -> CpuError {
+        CpuError::UnknownOpcode {
+            opcode,
+            address: self.pc,
+        }
+    }
+
+    /// ## 0x0NNN
+    /// Calls the RCA 1802 machine code at NNN (see [`Quirks::machine_code`]).
+    /// Registers are set up as the VIP interpreter does: P = 3 is the program
+    /// counter, X = 2 the stack, R5 the CHIP-8 PC, R6 and R7 point to VX and VY,
+    /// RA holds I and RB the display buffer. The routine returns with `SEP R4`.
+    fn op_0nnn(&mut self, opcode: u16) -> Result<(), CpuError> {
+        self.profile("0nnn");
+        self.inc_pc();
+        if !self.quirks.machine_code {
+            warn!("0x0: Ignoring unrecognized opcode {:#X}", opcode);
+            return Ok(());
+        }
+
+        let address = self.pc - 2;
+        let x = (opcode & 0x0F00) >> 8;
+        let y = (opcode & 0x00F0) >> 4;
+        self.store_vip_state();
+
+        let mut cdp1802 = Cdp1802::new();
+        cdp1802.p = 3;
+        cdp1802.x = 2;
+        cdp1802.r[2] = VIP_STACK_ADDRESS;
+        cdp1802.r[3] = opcode & 0x0FFF;
+        cdp1802.r[5] = self.pc;
+        cdp1802.r[6] = VIP_REGISTERS_ADDRESS + x;
+        cdp1802.r[7] = VIP_REGISTERS_ADDRESS + y;
+        cdp1802.r[0xA] = self.i as u16;
+        cdp1802.r[0xB] = VIP_DISPLAY_ADDRESS;
+
+        while cdp1802.p != 4 {
+            if cdp1802.cycles > MACHINE_CODE_CYCLE_LIMIT {
+                return Err(CpuError::MachineCodeTimeout { address });
+            }
+            cdp1802.step(self.bus.as_mut());
+        }
+
+        self.pc = cdp1802.r[5] & 0x0FFF;
+        self.i = (cdp1802.r[0xA] & 0x0FFF) as u32;
+        self.load_vip_state();
+        Ok(())
+    }
+
+    /// Copies the registers and the screen where VIP machine code finds them,
+    /// the screen as 1 bit per pixel. Only the 64x32 screen has a display
+    /// buffer there.
+    fn store_vip_state(&mut self) {
+        let registers = VIP_REGISTERS_ADDRESS as usize;
+        self.bus.memory_mut()[registers..registers + 16].copy_from_slice(&self.v);
+        if self.screen.len() != SCREEN_WIDTH * SCREEN_HEIGHT {
+            return;
+        }
+
+        let display = VIP_DISPLAY_ADDRESS as usize;
+        for (index, pixels) in self.screen.chunks(8).enumerate() {
+            self.bus.memory_mut()[display + index] = pixels
+                .iter()
+                .fold(0, |byte, pixel| (byte << 1) | (*pixel != 0) as u8);
+        }
+    }
+
+    /// Reads back what machine code left in the VIP registers and screen.
+    fn load_vip_state(&mut self) {
+        let registers = VIP_REGISTERS_ADDRESS as usize;
+        self.v
+            .copy_from_slice(&self.bus.memory()[registers..registers + 16]);
+        if self.screen.len() != SCREEN_WIDTH * SCREEN_HEIGHT {
+            return;
+        }
+
+        let display = VIP_DISPLAY_ADDRESS as usize;
+        for (index, pixel) in self.screen.iter_mut().enumerate() {
+            let byte = self.bus.memory()[display + index / 8];
+            let lit = if byte & (0x80 >> (index % 8)) != 0 {
+                0xFF
+            } else {
+                0
+            };
+            if *pixel != lit {
+                *pixel = lit;
+                self.draw_flag = true;
+            }
         }
     }
 
     /// ## 0x00E0
-    /// Clears the screen.
+    /// Clears the screen. In MegaChip mode, shows the frame drawn since the
+    /// last one first.
     fn op_00e0(&mut self) {
-        self.print_debug("00e0");
+        self.profile("00e0");
         for pixel in self.screen.iter_mut() {
             *pixel = 0;
         }
+        if let Some(megachip) = self.megachip.as_mut().filter(|megachip| megachip.enabled) {
+            megachip.present();
+        }
+        self.draw_flag = true;
+
+        self.inc_pc();
+    }
+
+    /// ## 0x02A0
+    /// CHIP-8X: cycles the background color.
+    fn op_02a0(&mut self) {
+        self.profile("02a0");
+        if let Some(colors) = &mut self.colors {
+            colors.cycle_background();
+        }
         self.draw_flag = true;
+        self.inc_pc();
+    }
+
+    /// The MegaChip opcodes, all in the `0NNN` range:
+    /// - `0010`/`0011`: leaves/enters MegaChip mode
+    /// - `01NN NNNN`: sets I to the 24-bit NNNNNN
+    /// - `02NN`: loads NN colors from I into the palette
+    /// - `03NN`/`04NN`: sets the sprite width/height (0 is 256)
+    /// - `05NN`: sets the screen opacity
+    /// - `060N`: plays the digitised sound at I, once if N is 1
+    /// - `0700`: stops the sound
+    /// - `080N`: sets the blend mode
+    /// - `09NN`: sets the collision color
+    fn op_megachip(&mut self, opcode: u16) {
+        self.profile("megachip");
+        let nn = (opcode & 0x00FF) as u8;
+        let size = |nn: u8| if nn == 0 { 256 } else { nn as usize };
+        let Some(megachip) = &mut self.megachip else {
+            return;
+        };
 
+        match opcode >> 8 {
+            0x00 => {
+                megachip.enabled = opcode == 0x0011;
+                let (width, height) = if megachip.enabled {
+                    (megachip::WIDTH, megachip::HEIGHT)
+                } else {
+                    self.platform.resolution()
+                };
+                self.set_resolution(width, height);
+            }
+            0x01 => {
+                let pc = self.pc as u32;
+                let low = ((self.read(pc + 2) as u32) << 8) | self.read(pc + 3) as u32;
+                self.i = ((nn as u32) << 16) | low;
+                self.inc_pc();
+            }
+            0x02 => {
+                let memory = self.bus.memory();
+                let start = (self.i as usize).min(memory.len());
+                megachip.load_palette(&memory[start..], size(nn).min(255));
+            }
+            0x03 => megachip.sprite_width = size(nn),
+            0x04 => megachip.sprite_height = size(nn),
+            0x05 => megachip.screen_alpha = nn,
+            0x06 => megachip.play_sound(self.bus.memory(), self.i, nn & 0xF == 1),
+            0x07 => megachip.stop_sound(),
+            0x08 => megachip.set_blend_mode(nn & 0xF),
+            _ => megachip.collision_color = nn,
+        }
         self.inc_pc();
     }
 
+    /// One frame, at `rate` samples per second, of the MegaChip sound that
+    /// is playing. Empty when none is.
+    pub fn sound_samples(&mut self, rate: u32) -> Vec<i16> {
+        match &mut self.megachip {
+            Some(megachip) => megachip.sound_samples(self.bus.memory(), (rate / 60) as usize, rate),
+            None => vec![],
+        }
+    }
+
     /// ## 0x00EE
     /// Returns from subroutine.
-    fn op_00ee(&mut self) {
-        self.print_debug("00ee");
-        if let Some(value) = self.stack.pop() {
-            self.pc = value;
-            self.inc_pc();
-        } else {
-            panic!("Tried to pop the stack but it is empty!");
+    fn op_00ee(&mut self) -> Result<(), CpuError> {
+        self.profile("00ee");
+        let Some(mut value) = self.stack.pop() else {
+            return Err(CpuError::StackUnderflow { address: self.pc });
+        };
+        if self.stack_in_memory {
+            let slot = self.stack_slot(self.stack.len());
+            value = u16::from_be_bytes([self.bus.read(slot), self.bus.read(slot + 1)]);
         }
+        self.pc = value;
+        self.inc_pc();
+        Ok(())
     }
 
     /// ## 0x1NNN
     /// Jumps to address NNN (does not increment stack).
+    /// HIRES programs start with a jump to `0260`, which switches to 64x64.
     fn op_1nnn(&mut self, nnn: u16) {
-        self.print_debug("1nnn");
+        self.profile("1nnn");
+        if self.platform == Platform::Hires && self.pc == PROGRAM_START && nnn == HIRES_TRAMPOLINE {
+            self.set_resolution(SCREEN_WIDTH, SCREEN_WIDTH);
+            self.pc = HIRES_PROGRAM_START;
+            return;
+        }
         self.pc = nnn;
     }
 
     /// ## 0x2NNN
     /// Calls subroutine on address NNN and increments the stack.
-    fn op_2nnn(&mut self, nnn: u16) {
-        self.print_debug("2nnn");
+    fn op_2nnn(&mut self, nnn: u16) -> Result<(), CpuError> {
+        self.profile("2nnn");
+        if self.stack.len() >= self.stack_depth {
+            return Err(CpuError::StackOverflow { address: self.pc });
+        }
+        if self.stack_in_memory {
+            let slot = self.stack_slot(self.stack.len());
+            let [high, low] = self.pc.to_be_bytes();
+            self.bus.write(slot, high);
+            self.bus.write(slot + 1, low);
+        }
         self.stack.push(self.pc);
         self.pc = nnn;
+        Ok(())
+    }
+
+    /// Where return address number `level` is kept with [`Cpu::stack_in_memory`].
+    fn stack_slot(&self, level: usize) -> u32 {
+        VIP_CALL_STACK_ADDRESS as u32 + level as u32 * 2
     }
 
     /// ## 0x3XNN
     /// Skips next instruction if VX equals NN.
     fn op_3xnn(&mut self, x: usize, nn: u8) {
-        self.print_debug("3xnn");
+        self.profile("3xnn");
         if self.v[x] == nn {
             self.inc_pc();
         }
@@ -335,7 +753,7 @@ impl Cpu {
     /// ## 0x4XNN
     /// Skips next instruction if VX not equals NN.
     fn op_4xnn(&mut self, x: usize, nn: u8) {
-        self.print_debug("4xnn");
+        self.profile("4xnn");
         if self.v[x] != nn {
             self.inc_pc();
         }
@@ -345,17 +763,27 @@ impl Cpu {
     /// ## 0x5XY0
     /// Skips next instruction if VX equals VY.
     fn op_5xy0(&mut self, x: usize, y: usize) {
-        self.print_debug("5xy0");
+        self.profile("5xy0");
         if self.v[x] == self.v[y] {
             self.inc_pc();
         }
         self.inc_pc();
     }
 
+    /// ## 0x5XY1
+    /// CHIP-8X: adds VY to VX nibble by nibble, each sum modulo 8.
+    fn op_5xy1(&mut self, x: usize, y: usize) {
+        self.profile("5xy1");
+        let high = ((self.v[x] >> 4) + (self.v[y] >> 4)) & 0x7;
+        let low = ((self.v[x] & 0xF) + (self.v[y] & 0xF)) & 0x7;
+        self.v[x] = (high << 4) | low;
+        self.inc_pc();
+    }
+
     /// ## 0x6XNN
     /// Sets V[X] to NN
     fn op_6xnn(&mut self, x: usize, nn: u8) {
-        self.print_debug("6xnn");
+        self.profile("6xnn");
         self.v[x] = nn;
         self.inc_pc();
     }
@@ -363,7 +791,7 @@ impl Cpu {
     /// ## 0x7XNN
     /// Adds NN to VX (Does not change carry flag)
     fn op_7xnn(&mut self, x: usize, nn: u8) {
-        self.print_debug("7xnn");
+        self.profile("7xnn");
         self.v[x] = self.v[x].wrapping_add(nn);
         self.inc_pc();
     }
@@ -371,7 +799,7 @@ impl Cpu {
     /// ## 0x8XY0
     /// Sets VX to the value of VY
     fn op_8xy0(&mut self, x: usize, y: usize) {
-        self.print_debug("8xy0");
+        self.profile("8xy0");
         self.v[x] = self.v[y];
         self.inc_pc();
     }
@@ -379,34 +807,40 @@ impl Cpu {
     /// ## 0x8XY1
     /// Sets VX to (VX 'OR' VY)
     fn op_8xy1(&mut self, x: usize, y: usize) {
-        self.print_debug("8xy1");
-        self.v[0xF] = 0; // original chip8 quirk: reset flag register to zero.
-        self.v[x] = self.v[x] | self.v[y];
+        self.profile("8xy1");
+        self.v[x] |= self.v[y];
+        if self.quirks.vf_reset {
+            self.v[0xF] = 0;
+        }
         self.inc_pc();
     }
 
     /// ## 0x8XY2
     /// Sets VX to (VX 'AND' VY)
     fn op_8xy2(&mut self, x: usize, y: usize) {
-        self.print_debug("8xy2");
-        self.v[0xF] = 0; // original chip8 quirk: reset flag register to zero.
-        self.v[x] = self.v[x] & self.v[y];
+        self.profile("8xy2");
+        self.v[x] &= self.v[y];
+        if self.quirks.vf_reset {
+            self.v[0xF] = 0;
+        }
         self.inc_pc();
     }
 
     /// ## 0x8XY3
     /// Sets VX to (VX 'XOR' VY)
     fn op_8xy3(&mut self, x: usize, y: usize) {
-        self.print_debug("8xy3");
-        self.v[0xF] = 0; // original chip8 quirk: reset flag register to zero.
-        self.v[x] = self.v[x] ^ self.v[y];
+        self.profile("8xy3");
+        self.v[x] ^= self.v[y];
+        if self.quirks.vf_reset {
+            self.v[0xF] = 0;
+        }
         self.inc_pc();
     }
 
     /// ## 0x8XY4
     /// Sets VX = VX + VY, VF = carry flag
     fn op_8xy4(&mut self, x: usize, y: usize) {
-        self.print_debug("8xy4");
+        self.profile("8xy4");
         let sum: u16 = self.v[x] as u16 + self.v[y] as u16;
 
         let carry_flag = if sum > 255 { 1 } else { 0 };
@@ -419,7 +853,7 @@ impl Cpu {
     /// ## 0x8XY5
     /// Sets VX = VX - VY, VF = not borrow flag
     fn op_8xy5(&mut self, x: usize, y: usize) {
-        self.print_debug("8xy5");
+        self.profile("8xy5");
         let (diff, overflow) = self.v[x].overflowing_sub(self.v[y]);
 
         self.v[x] = diff;
@@ -430,13 +864,15 @@ impl Cpu {
     /// ## 0x8XY6
     /// Set VX = VX SHIFT RIGHT 1, VF = the least significant bit.
     fn op_8xy6(&mut self, x: usize, y: usize) {
-        self.print_debug("8xy6");
-        self.v[x] = self.v[y]; // original chip8 quirk: set VX to VY
+        self.profile("8xy6");
+        if !self.quirks.shift_ignores_vy {
+            self.v[x] = self.v[y];
+        }
         let least_bit = self.v[x] & 0b0000_0001;
 
         let carry_flag = if least_bit == 0 { 0 } else { 1 };
 
-        self.v[x] = self.v[x] >> 1;
+        self.v[x] >>= 1;
         self.v[0xF] = carry_flag;
         self.inc_pc();
     }
@@ -444,7 +880,7 @@ impl Cpu {
     /// ## 0x8XY7
     /// Set VX = VY - VX. VF = not borrow flag.
     fn op_8xy7(&mut self, x: usize, y: usize) {
-        self.print_debug("8xy7");
+        self.profile("8xy7");
         let (diff, overflow) = self.v[y].overflowing_sub(self.v[x]);
 
         self.v[x] = diff;
@@ -455,13 +891,15 @@ impl Cpu {
     /// ## 0x8XYE
     /// Set VX = VX SHIFT LEFT 1, VF = the most significant bit.
     fn op_8xye(&mut self, x: usize, y: usize) {
-        self.print_debug("8xye");
-        self.v[x] = self.v[y]; // original chip8 quirk: set VX to VY
+        self.profile("8xye");
+        if !self.quirks.shift_ignores_vy {
+            self.v[x] = self.v[y];
+        }
         let most_bit = self.v[x] & 0b1000_0000;
 
         let carry_flag = if most_bit == 0 { 0 } else { 1 };
 
-        self.v[x] = self.v[x] << 1;
+        self.v[x] <<= 1;
         self.v[0xF] = carry_flag;
         self.inc_pc();
     }
@@ -469,7 +907,7 @@ impl Cpu {
     /// ## 0x9XY0
     /// Skip next instruction if VX != VY
     fn op_9xy0(&mut self, x: usize, y: usize) {
-        self.print_debug("9xy0");
+        self.profile("9xy0");
         if self.v[x] != self.v[y] {
             self.inc_pc();
         }
@@ -479,22 +917,46 @@ impl Cpu {
     /// ## 0xANNN
     /// Sets I to NNN
     fn op_annn(&mut self, nnn: u16) {
-        self.print_debug("annn");
-        self.i = nnn;
+        self.profile("annn");
+        self.i = nnn as u32;
         self.inc_pc();
     }
 
     /// ## 0xBNNN
-    /// Jumps to address NNN + V0
+    /// Jumps to address NNN + V0 (or XNN + VX, see [`Quirks::jump_uses_vx`]).
     fn op_bnnn(&mut self, nnn: u16) {
-        self.print_debug("bnnn");
-        self.pc = nnn + (self.v[0] as u16);
+        self.profile("bnnn");
+        let offset_register = if self.quirks.jump_uses_vx {
+            ((nnn & 0x0F00) >> 8) as usize
+        } else {
+            0
+        };
+        self.pc = nnn + (self.v[offset_register] as u16);
+    }
+
+    /// ## 0xBXYN
+    /// CHIP-8X: sets the foreground color to V(X+1). `BXY0` colors bands
+    /// of 4 rows given by VX (columns) and VY (bands), `BXYN` N rows from
+    /// pixel (VX, VY).
+    fn op_bxyn(&mut self, x: usize, y: usize, n: u8) {
+        self.profile("bxyn");
+        let color = self.v[(x + 1) & 0xF];
+        let (vx, vy) = (self.v[x], self.v[y]);
+        if let Some(colors) = &mut self.colors {
+            if n == 0 {
+                colors.fill_bands(vx, vy, color);
+            } else {
+                colors.fill_rows(vx, vy, n, color);
+            }
+        }
+        self.draw_flag = true;
+        self.inc_pc();
     }
 
     /// ## 0xCXNN
     /// Sets VX to a random number[0-255] bitwise `AND` NN.
     fn op_cxnn(&mut self, x: usize, nn: u8) {
-        self.print_debug("cxnn");
+        self.profile("cxnn");
         let random_num: u8 = self.rng.random();
         self.v[x] = random_num & nn;
         self.inc_pc();
@@ -502,9 +964,37 @@ impl Cpu {
 
     /// ## 0xDXYN
     /// Draws to the screen and checks when there's pixel collision.
-    fn op_dxyn(&mut self, x: usize, y: usize, height: u8) {
-        let x_pos = self.v[x] % (SCREEN_WIDTH as u8);
-        let y_pos = self.v[y] % (SCREEN_HEIGHT as u8);
+    fn op_dxyn(&mut self, x: usize, y: usize, height: u8) -> Result<(), CpuError> {
+        if self.quirks.display_wait {
+            if !self.vblank {
+                // Runs again once the frame is over.
+                self.waiting_for_vblank = true;
+                return Ok(());
+            }
+            self.vblank = false;
+        }
+
+        self.profile("dxyn");
+        self.profiler.draw_calls += 1;
+        if let Some(megachip) = self.megachip.as_mut().filter(|megachip| megachip.enabled) {
+            let size = megachip.sprite_width * megachip.sprite_height;
+            let memory = self.bus.memory();
+            let start = (self.i as usize).min(memory.len());
+            let end = (start + size).min(memory.len());
+            for address in start..end {
+                self.coverage.mark_read(address as u32);
+            }
+            self.profiler.sprite_rows += megachip.sprite_height as u64;
+            let (x_pos, y_pos) = (self.v[x] as usize, self.v[y] as usize);
+            let collision = megachip.draw_sprite(&self.bus.memory()[start..end], x_pos, y_pos);
+            self.v[0xF] = collision as u8;
+            self.draw_flag = true;
+            self.inc_pc();
+            return Ok(());
+        }
+        self.profiler.sprite_rows += height as u64;
+        let x_pos = self.v[x] as usize % self.width;
+        let y_pos = self.v[y] as usize % self.height;
 
         // println!("drawing at ({}, {}) sprite {}x8", x_pos, y_pos, height);
 
@@ -512,37 +1002,39 @@ impl Cpu {
         self.v[0xF] = 0;
 
         for row in 0..height {
+            let y = y_pos + row as usize;
             // Clip sprite if it goes past the bottom of the screen.
-            if (y_pos + row) >= (SCREEN_HEIGHT as u8) {
+            if self.quirks.clip_sprites && y >= self.height {
                 // println!("skipping drawing at row {}", row);
                 break;
             }
-            let mut pixel = self.read(self.i + (row as u16));
+            let mut pixel = self.read_data(self.i + (row as u32))?;
 
             // Width is 8 bytes
             for col in 0..8 {
                 // Clip sprite if it goes past the left side of the screen.
-                if (x_pos + col) >= (SCREEN_WIDTH as u8) {
+                if self.quirks.clip_sprites && (x_pos + col) >= self.width {
                     // println!("skipping drawing at col {}, row {}", col, row);
                     break;
                 }
 
-                if self.set_screen_pixel(x_pos + col, y_pos + row, (pixel & 0b1000_0000) >> 7) {
+                if self.set_screen_pixel(x_pos + col, y, (pixel & 0b1000_0000) >> 7) {
                     self.v[0xF] = 1; // There was pixel colision.
                 }
 
-                pixel = pixel << 1;
+                pixel <<= 1;
             }
         }
 
         self.draw_flag = true;
         self.inc_pc();
+        Ok(())
     }
 
     /// ## 0xEX9E
     /// Skips the next instruction if the key in VX is pressed.
     fn op_ex9e(&mut self, x: usize) {
-        self.print_debug("ex9e");
+        self.profile("ex9e");
         let key = self.keys[self.v[x] as usize];
         if key {
             self.inc_pc();
@@ -553,7 +1045,7 @@ impl Cpu {
     /// ## 0xEXA1
     /// Skips the next instruction if the key in VX is NOT pressed.
     fn op_exa1(&mut self, x: usize) {
-        self.print_debug("exa1");
+        self.profile("exa1");
         let key = self.keys[self.v[x] as usize];
         if !key {
             self.inc_pc();
@@ -561,10 +1053,32 @@ impl Cpu {
         self.inc_pc();
     }
 
+    /// ## 0xEXF2
+    /// CHIP-8X: skips the next instruction if the key in VX is pressed on
+    /// the second keypad.
+    fn op_exf2(&mut self, x: usize) {
+        self.profile("exf2");
+        if self.keys2[self.v[x] as usize & 0xF] {
+            self.inc_pc();
+        }
+        self.inc_pc();
+    }
+
+    /// ## 0xEXF5
+    /// CHIP-8X: skips the next instruction if the key in VX is NOT pressed on
+    /// the second keypad.
+    fn op_exf5(&mut self, x: usize) {
+        self.profile("exf5");
+        if !self.keys2[self.v[x] as usize & 0xF] {
+            self.inc_pc();
+        }
+        self.inc_pc();
+    }
+
     /// ## 0xFX07
     /// Sets VX to the value in the delay timer.
     fn op_fx07(&mut self, x: usize) {
-        self.print_debug("fx07");
+        self.profile("fx07");
         self.v[x] = self.delay_timer;
         self.inc_pc();
     }
@@ -573,13 +1087,13 @@ impl Cpu {
     /// Waits for a key press and then stores that key in VX.
     /// We only resume once the key is released.
     fn op_fx0a(&mut self, x: usize) {
-        self.print_debug("fx0a");
+        self.profile("fx0a");
+        let pc = self.pc;
         if let Some(key_index) = self.pressed_key_index {
             if !self.keys[key_index] {
                 self.pressed_key_index = None;
                 self.v[x] = key_index as u8;
                 self.inc_pc();
-                return;
             }
         } else {
             for i in 0..16 {
@@ -588,12 +1102,16 @@ impl Cpu {
                 }
             }
         }
+
+        if self.pc == pc {
+            self.profiler.key_wait_instructions += 1;
+        }
     }
 
     /// ## 0xFX15
     /// Sets delay timer to VX.
     fn op_fx15(&mut self, x: usize) {
-        self.print_debug("fx15");
+        self.profile("fx15");
         self.delay_timer = self.v[x];
         self.inc_pc();
     }
@@ -601,7 +1119,7 @@ impl Cpu {
     /// ## 0xFX18
     /// Sets sound timer to VX.
     fn op_fx18(&mut self, x: usize) {
-        self.print_debug("fx18");
+        self.profile("fx18");
         self.sound_timer = self.v[x];
         self.inc_pc();
     }
@@ -609,16 +1127,16 @@ impl Cpu {
     /// ## 0xFX1E
     /// Adds VX to I, does not affect VF(carry flag).
     fn op_fx1e(&mut self, x: usize) {
-        self.print_debug("fx1e");
-        self.i += self.v[x] as u16;
+        self.profile("fx1e");
+        self.i += self.v[x] as u32;
         self.inc_pc();
     }
 
     /// ## 0xFX29
     /// Sets I to the address of the sprite for digit in VX.
     fn op_fx29(&mut self, x: usize) {
-        self.print_debug("fx29");
-        self.i = FONTSET_START_ADDRESS + (self.v[x] as u16 * 5);
+        self.profile("fx29");
+        self.i = (self.font_address + (self.v[x] as u16 * 5)) as u32;
         self.inc_pc();
     }
 
@@ -627,52 +1145,258 @@ impl Cpu {
     /// ### Example:
     /// Let VX = 0xFE => 254 in decimal.
     /// Then... I = 2, I+1 = 5, I+2 = 4
-    fn op_fx33(&mut self, x: usize) {
-        self.print_debug("fx33");
+    fn op_fx33(&mut self, x: usize) -> Result<(), CpuError> {
+        self.profile("fx33");
         let mut value = self.v[x];
-        self.write(self.i + 2, value % 10);
+        self.write_data(self.i + 2, value % 10)?;
         value /= 10;
 
-        self.write(self.i + 1, value % 10);
+        self.write_data(self.i + 1, value % 10)?;
         value /= 10;
 
-        self.write(self.i, value % 10);
+        self.write_data(self.i, value % 10)?;
         self.inc_pc();
+        Ok(())
     }
 
     /// ## 0xFX55
     /// Stores the bytes from V0 to VX(inclusive) into memory starting from the address stored in I.
-    fn op_fx55(&mut self, x: usize) {
-        self.print_debug("fx55");
+    fn op_fx55(&mut self, x: usize) -> Result<(), CpuError> {
+        self.profile("fx55");
         for offset in 0..x + 1 {
-            self.write(self.i + offset as u16, self.v[offset]);
+            self.write_data(self.i + offset as u32, self.v[offset])?;
+        }
+        if !self.quirks.load_store_keeps_i {
+            self.i += x as u32 + 1;
         }
-        self.i += 1; // original chip8 quirk: I is incremented after save.
         self.inc_pc();
+        Ok(())
     }
 
     /// ## 0xFX65
     /// Fills V0 to VX(inclusive) with bytes starting from the address stored in I.
-    fn op_fx65(&mut self, x: usize) {
-        self.print_debug("fx65");
+    fn op_fx65(&mut self, x: usize) -> Result<(), CpuError> {
+        self.profile("fx65");
         for offset in 0..x + 1 {
-            self.v[offset] = self.read(self.i + offset as u16);
+            self.v[offset] = self.read_data(self.i + offset as u32)?;
         }
-        self.i += 1; // original chip8 quirk: I is incremented after load.
+        if !self.quirks.load_store_keeps_i {
+            self.i += x as u32 + 1;
+        }
+        self.inc_pc();
+        Ok(())
+    }
+
+    /// ## 0xFXF8
+    /// CHIP-8X: sends VX to output port 3, which sets the tone of the
+    /// VP-595 sound board. The buzzer has a single tone, so it's ignored.
+    fn op_fxf8(&mut self, _x: usize) {
+        self.profile("fxf8");
+        self.inc_pc();
+    }
+
+    /// ## 0xFXFB
+    /// CHIP-8X: waits for input port 3 and stores it in VX. Nothing is
+    /// plugged in there, so it reads 0.
+    fn op_fxfb(&mut self, x: usize) {
+        self.profile("fxfb");
+        self.v[x] = 0;
         self.inc_pc();
     }
 }
 
+impl Default for Cpu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl fmt::Debug for Cpu {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::result::Result<(), std::fmt::Error> {
-        write!(f, "PC: {:#X} | I: {:#X}\n", self.pc, self.i)?;
+        writeln!(f, "PC: {:#X} | I: {:#X}", self.pc, self.i)?;
 
         write!(f, "Registers: ")?;
         for vx in self.v.iter() {
             write!(f, "{:#X}|", vx)?;
         }
         write!(f, "\nStack: {:?}\n", self.stack)?;
+        if let Some(violation) = &self.last_violation {
+            writeln!(f, "Last memory violation: {}", violation)?;
+        }
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A CPU for `platform` with `program` loaded at its program start.
+    fn cpu_with(platform: Platform, program: &[u8]) -> Cpu {
+        let mut cpu = Cpu::new();
+        cpu.set_platform(platform);
+        cpu.load_rom(program);
+        cpu
+    }
+
+    #[test]
+    fn set_platform_resizes_profile_and_coverage() {
+        let mut cpu = cpu_with(Platform::MegaChip, &[]);
+        let size = Platform::MegaChip.memory_size();
+        assert_eq!(cpu.profiler.executions.len(), size);
+
+        cpu.i = 0x10000;
+        cpu.op_fx65(0).unwrap();
+        assert_eq!(cpu.coverage.flags(0x10000), coverage::READ);
+    }
+
+    #[test]
+    fn machine_code_is_ignored_by_default() {
+        // 0204 calls the routine at 204: LDI 42, STR R6 (V2), SEP R4.
+        let program = [0x02, 0x04, 0x00, 0x00, 0xF8, 0x42, 0x56, 0xD4];
+        let mut cpu = cpu_with(Platform::SuperChip, &program);
+        cpu.quirks = Platform::SuperChip.quirks();
+        cpu.run_instruction().unwrap();
+        assert_eq!(cpu.pc, 0x202);
+        assert_eq!(cpu.v[2], 0);
+        assert_eq!(cpu.read(VIP_REGISTERS_ADDRESS as u32 + 2), 0);
+    }
+
+    #[test]
+    fn machine_code_returns_to_the_interpreter() {
+        let program = [0x02, 0x04, 0x00, 0x00, 0xF8, 0x42, 0x56, 0xD4];
+        let mut cpu = cpu_with(Platform::Chip8, &program);
+        cpu.quirks = Platform::Chip8.quirks();
+        cpu.run_instruction().unwrap();
+        assert_eq!(cpu.pc, 0x202);
+        assert_eq!(cpu.v[2], 0x42);
+    }
+
+    #[test]
+    fn calls_past_the_stack_depth_overflow() {
+        // 2200 calls itself until the stack is full.
+        let mut cpu = cpu_with(Platform::Chip8, &[0x22, 0x00]);
+        for _ in 0..Platform::Chip8.stack_depth() {
+            cpu.run_instruction().unwrap();
+        }
+        assert_eq!(
+            cpu.run_instruction(),
+            Err(CpuError::StackOverflow { address: 0x200 })
+        );
+    }
+
+    #[test]
+    fn the_in_memory_stack_stays_below_the_1802_stack() {
+        let mut cpu = cpu_with(Platform::Chip8, &[0x22, 0x00]);
+        cpu.stack_depth = VIP_CALL_STACK_DEPTH;
+        cpu.stack_in_memory = true;
+        cpu.write(VIP_STACK_ADDRESS as u32, 0xAA);
+        for _ in 0..VIP_CALL_STACK_DEPTH {
+            cpu.run_instruction().unwrap();
+        }
+        assert_eq!(
+            cpu.run_instruction(),
+            Err(CpuError::StackOverflow { address: 0x200 })
+        );
+        assert_eq!(cpu.read(VIP_CALL_STACK_ADDRESS as u32), 0x02);
+        // The last return address is at ECC-ECD.
+        assert_eq!(cpu.read(VIP_STACK_ADDRESS as u32 - 3), 0x02);
+        assert_eq!(cpu.read(VIP_STACK_ADDRESS as u32 - 2), 0x00);
+        assert_eq!(cpu.read(VIP_STACK_ADDRESS as u32), 0xAA);
+    }
+
+    #[test]
+    fn the_in_memory_stack_returns_to_patched_addresses() {
+        // 2204, then at 204: 00EE.
+        let mut cpu = cpu_with(Platform::Chip8, &[0x22, 0x04, 0x00, 0x00, 0x00, 0xEE]);
+        cpu.stack_in_memory = true;
+        cpu.run_instruction().unwrap();
+        cpu.write(VIP_CALL_STACK_ADDRESS as u32 + 1, 0x40);
+        cpu.run_instruction().unwrap();
+        assert_eq!(cpu.pc, 0x242);
+    }
+
+    #[test]
+    fn returns_with_an_empty_stack_underflow() {
+        let mut cpu = cpu_with(Platform::Chip8, &[0x00, 0xEE]);
+        assert_eq!(
+            cpu.run_instruction(),
+            Err(CpuError::StackUnderflow { address: 0x200 })
+        );
+    }
+
+    #[test]
+    fn accesses_wrap_around_memory_by_default() {
+        // 6001 6102 AFFF F155: V0 at FFF, V1 at 000.
+        let program = [0x60, 0x01, 0x61, 0x02, 0xAF, 0xFF, 0xF1, 0x55];
+        let mut cpu = cpu_with(Platform::Chip8, &program);
+        for _ in 0..4 {
+            cpu.run_instruction().unwrap();
+        }
+        assert_eq!(cpu.read(0xFFF), 0x01);
+        assert_eq!(cpu.read(0x000), 0x02);
+        assert_eq!(cpu.last_violation, None);
+    }
+
+    #[test]
+    fn out_of_range_accesses_are_violations() {
+        let program = [0x60, 0x01, 0x61, 0x02, 0xAF, 0xFF, 0xF1, 0x55];
+        let mut cpu = cpu_with(Platform::Chip8, &program);
+        cpu.memory_policy.out_of_range = true;
+        let font = cpu.read(0x000);
+        for _ in 0..4 {
+            cpu.run_instruction().unwrap();
+        }
+        assert_eq!(cpu.read(0x000), font);
+        assert_eq!(
+            cpu.last_violation,
+            Some(CpuError::AddressOutOfRange {
+                address: 0x206,
+                target: 0x1000
+            })
+        );
+
+        let mut cpu = cpu_with(Platform::Chip8, &program);
+        cpu.memory_policy.out_of_range = true;
+        cpu.memory_policy.stop = true;
+        for _ in 0..3 {
+            cpu.run_instruction().unwrap();
+        }
+        assert!(cpu.run_instruction().is_err());
+    }
+
+    #[test]
+    fn protected_writes_are_dropped() {
+        // 60FF A100 F033: the digits of 255 below the program start.
+        let mut cpu = cpu_with(Platform::Chip8, &[0x60, 0xFF, 0xA1, 0x00, 0xF0, 0x33]);
+        cpu.memory_policy.protect_interpreter = true;
+        for _ in 0..3 {
+            cpu.run_instruction().unwrap();
+        }
+        assert_eq!(cpu.memory()[0x100..0x103], [0, 0, 0]);
+        assert_eq!(
+            cpu.last_violation,
+            Some(CpuError::ProtectedWrite {
+                address: 0x204,
+                target: 0x100
+            })
+        );
+        assert_eq!(cpu.pc, 0x206);
+    }
+
+    #[test]
+    fn executing_data_is_a_violation() {
+        // A204 F065 reads the 6001 at 204, which then runs.
+        let mut cpu = cpu_with(Platform::Chip8, &[0xA2, 0x04, 0xF0, 0x65, 0x60, 0x01]);
+        cpu.memory_policy.data_execution = true;
+        for _ in 0..3 {
+            cpu.run_instruction().unwrap();
+        }
+        assert_eq!(
+            cpu.last_violation,
+            Some(CpuError::DataExecuted { address: 0x204 })
+        );
+        assert_eq!(cpu.v[0], 0x01);
+    }
+}
@@ -0,0 +1,181 @@
+use super::{
+    cpu::Cpu,
+    disasm::{self, Instruction},
+    error::CpuError,
+};
+
+/// Instructions shown before and after the one at PC.
+const DISASSEMBLY_CONTEXT: u16 = 5;
+/// Column where the disassembly starts, right of the registers.
+const DISASSEMBLY_COLUMN: usize = 25;
+const MEMORY_LINE: usize = 12;
+const MEMORY_ROWS: usize = 8;
+const BYTES_PER_ROW: usize = 16;
+/// Column of the first byte of a memory row, after `0300 `.
+const MEMORY_BYTES_COLUMN: usize = 5;
+const STACK_ENTRIES_PER_LINE: usize = 4;
+const STACK_LINES: usize = 2;
+
+/// Characters to draw highlighted, on a line of a [`DebugView`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Highlight {
+    pub line: usize,
+    pub column: usize,
+    pub len: usize,
+}
+
+/// The state of the CPU laid out as lines of text: registers, timers,
+/// stack, held keys and the last memory violation on the left, the disassembly around PC on the right,
+/// and the memory around I below. The instruction at PC and the bytes it
+/// uses at I are highlighted.
+pub struct DebugView {
+    pub lines: Vec<String>,
+    pub highlights: Vec<Highlight>,
+}
+
+impl DebugView {
+    pub fn capture(cpu: &Cpu) -> Self {
+        let mut view = DebugView {
+            lines: vec![],
+            highlights: vec![],
+        };
+        view.registers(cpu);
+        view.disassembly(cpu);
+        view.memory(cpu);
+        view
+    }
+
+    /// Writes `text` at the given position, growing the view as needed.
+    fn put(&mut self, line: usize, column: usize, text: &str) {
+        if self.lines.len() <= line {
+            self.lines.resize(line + 1, String::new());
+        }
+        let current = &mut self.lines[line];
+        let width = current.chars().count();
+        if width < column {
+            current.push_str(&" ".repeat(column - width));
+        }
+        current.push_str(text);
+    }
+
+    fn registers(&mut self, cpu: &Cpu) {
+        self.put(0, 0, &format!("PC {:04X}  I {:04X}", cpu.pc, cpu.i));
+        self.put(
+            1,
+            0,
+            &format!("DT {:02X}  ST {:02X}", cpu.delay_timer, cpu.sound_timer),
+        );
+
+        for (row, registers) in cpu.v.chunks(4).enumerate() {
+            let text: Vec<String> = registers
+                .iter()
+                .enumerate()
+                .map(|(index, value)| format!("V{:X} {:02X}", row * 4 + index, value))
+                .collect();
+            self.put(2 + row, 0, &text.join(" "));
+        }
+
+        let keys: String = (0..16)
+            .map(|key| {
+                if cpu.keys[key] {
+                    format!("{:X}", key)
+                } else {
+                    String::from(".")
+                }
+            })
+            .collect();
+        self.put(6, 0, &format!("Keys {}", keys));
+        if let Some(violation) = &cpu.last_violation {
+            self.put(7, 0, &Self::violation(violation));
+        }
+
+        // Most recent call first.
+        self.put(8, 0, &format!("Stack ({})", cpu.stack.len()));
+        let calls: Vec<String> = cpu
+            .stack
+            .iter()
+            .rev()
+            .take(STACK_ENTRIES_PER_LINE * STACK_LINES)
+            .map(|address| format!("{:04X}", address))
+            .collect();
+        for (line, chunk) in calls.chunks(STACK_ENTRIES_PER_LINE).enumerate() {
+            self.put(9 + line, 0, &chunk.join(" "));
+        }
+    }
+
+    /// A few words about a memory violation, to fit left of the disassembly.
+    fn violation(error: &CpuError) -> String {
+        match error {
+            CpuError::AddressOutOfRange { address, target } => {
+                format!("Range {:X} at {:04X}", target, address)
+            }
+            CpuError::ProtectedWrite { address, target } => {
+                format!("Write {:04X} at {:04X}", target, address)
+            }
+            CpuError::DataExecuted { address } => format!("Data run at {:04X}", address),
+            _ => error.to_string(),
+        }
+    }
+
+    fn disassembly(&mut self, cpu: &Cpu) {
+        let start = cpu.pc.saturating_sub(DISASSEMBLY_CONTEXT * 2);
+        for (line, address) in (start..)
+            .step_by(2)
+            .take(DISASSEMBLY_CONTEXT as usize * 2 + 1)
+            .enumerate()
+        {
+            let opcode = disasm::opcode_at(cpu.memory(), address);
+            let text = format!(
+                "{:04X} {:04X} {}",
+                address,
+                opcode,
+                Instruction::decode(opcode)
+            );
+            self.put(line, DISASSEMBLY_COLUMN, &text);
+            if address == cpu.pc {
+                self.highlights.push(Highlight {
+                    line,
+                    column: DISASSEMBLY_COLUMN,
+                    len: text.chars().count(),
+                });
+            }
+        }
+    }
+
+    fn memory(&mut self, cpu: &Cpu) {
+        let memory_size = cpu.memory().len();
+        let i = cpu.i as usize;
+        let first_row = (i / BYTES_PER_ROW)
+            .saturating_sub(2)
+            .min(memory_size / BYTES_PER_ROW - MEMORY_ROWS);
+
+        self.put(MEMORY_LINE - 1, 0, "Memory");
+        for row in 0..MEMORY_ROWS {
+            let address = (first_row + row) * BYTES_PER_ROW;
+            let bytes: Vec<String> = cpu.memory()[address..address + BYTES_PER_ROW]
+                .iter()
+                .map(|byte| format!("{:02X}", byte))
+                .collect();
+            self.put(
+                MEMORY_LINE + row,
+                0,
+                &format!("{:04X} {}", address, bytes.join(" ")),
+            );
+        }
+
+        // The bytes the instruction at PC is about to use, or just the one at I.
+        let used = Instruction::at(cpu.memory(), cpu.pc).i_bytes().max(1) as usize;
+        for address in i..i + used {
+            let Some(row) = (address / BYTES_PER_ROW).checked_sub(first_row) else {
+                continue;
+            };
+            if row < MEMORY_ROWS {
+                self.highlights.push(Highlight {
+                    line: MEMORY_LINE + row,
+                    column: MEMORY_BYTES_COLUMN + (address % BYTES_PER_ROW) * 3,
+                    len: 2,
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use std::fmt;
+
+/// A decoded CHIP-8 instruction. Registers are indexes into `Cpu::v`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Instruction {
+    /// `0NNN`: call a machine code routine.
+    Sys(u16),
+    /// `00E0`
+    Clear,
+    /// `00EE`
+    Return,
+    /// `1NNN`
+    Jump(u16),
+    /// `2NNN`
+    Call(u16),
+    /// `3XNN`
+    SkipEqual { x: usize, nn: u8 },
+    /// `4XNN`
+    SkipNotEqual { x: usize, nn: u8 },
+    /// `5XY0`
+    SkipEqualRegister { x: usize, y: usize },
+    /// `6XNN`
+    Load { x: usize, nn: u8 },
+    /// `7XNN`
+    Add { x: usize, nn: u8 },
+    /// `8XY0`
+    Move { x: usize, y: usize },
+    /// `8XY1`
+    Or { x: usize, y: usize },
+    /// `8XY2`
+    And { x: usize, y: usize },
+    /// `8XY3`
+    Xor { x: usize, y: usize },
+    /// `8XY4`
+    AddRegister { x: usize, y: usize },
+    /// `8XY5`
+    Sub { x: usize, y: usize },
+    /// `8XY6`
+    ShiftRight { x: usize, y: usize },
+    /// `8XY7`
+    SubReverse { x: usize, y: usize },
+    /// `8XYE`
+    ShiftLeft { x: usize, y: usize },
+    /// `9XY0`
+    SkipNotEqualRegister { x: usize, y: usize },
+    /// `ANNN`
+    LoadI(u16),
+    /// `BNNN`
+    JumpOffset(u16),
+    /// `CXNN`
+    Random { x: usize, nn: u8 },
+    /// `DXYN`
+    Draw { x: usize, y: usize, n: u8 },
+    /// `EX9E`
+    SkipKey { x: usize },
+    /// `EXA1`
+    SkipNotKey { x: usize },
+    /// `FX07`
+    LoadDelay { x: usize },
+    /// `FX0A`
+    WaitKey { x: usize },
+    /// `FX15`
+    SetDelay { x: usize },
+    /// `FX18`
+    SetSound { x: usize },
+    /// `FX1E`
+    AddI { x: usize },
+    /// `FX29`
+    LoadFont { x: usize },
+    /// `FX33`
+    Bcd { x: usize },
+    /// `FX55`
+    Store { x: usize },
+    /// `FX65`
+    Restore { x: usize },
+    /// Anything else.
+    Unknown(u16),
+}
+
+impl Instruction {
+    pub fn decode(opcode: u16) -> Instruction {
+        let x = ((opcode & 0x0F00) >> 8) as usize;
+        let y = ((opcode & 0x00F0) >> 4) as usize;
+        let n = (opcode & 0x000F) as u8;
+        let nn = (opcode & 0x00FF) as u8;
+        let nnn = opcode & 0x0FFF;
+
+        match (opcode & 0xF000, n) {
+            _ if opcode == 0x0000 => Instruction::Unknown(opcode),
+            _ if opcode == 0x00E0 => Instruction::Clear,
+            _ if opcode == 0x00EE => Instruction::Return,
+            (0x0000, _) => Instruction::Sys(nnn),
+            (0x1000, _) => Instruction::Jump(nnn),
+            (0x2000, _) => Instruction::Call(nnn),
+            (0x3000, _) => Instruction::SkipEqual { x, nn },
+            (0x4000, _) => Instruction::SkipNotEqual { x, nn },
+            (0x5000, 0x0) => Instruction::SkipEqualRegister { x, y },
+            (0x6000, _) => Instruction::Load { x, nn },
+            (0x7000, _) => Instruction::Add { x, nn },
+            (0x8000, 0x0) => Instruction::Move { x, y },
+            (0x8000, 0x1) => Instruction::Or { x, y },
+            (0x8000, 0x2) => Instruction::And { x, y },
+            (0x8000, 0x3) => Instruction::Xor { x, y },
+            (0x8000, 0x4) => Instruction::AddRegister { x, y },
+            (0x8000, 0x5) => Instruction::Sub { x, y },
+            (0x8000, 0x6) => Instruction::ShiftRight { x, y },
+            (0x8000, 0x7) => Instruction::SubReverse { x, y },
+            (0x8000, 0xE) => Instruction::ShiftLeft { x, y },
+            (0x9000, 0x0) => Instruction::SkipNotEqualRegister { x, y },
+            (0xA000, _) => Instruction::LoadI(nnn),
+            (0xB000, _) => Instruction::JumpOffset(nnn),
+            (0xC000, _) => Instruction::Random { x, nn },
+            (0xD000, _) => Instruction::Draw { x, y, n },
+            (0xE000, _) if nn == 0x9E => Instruction::SkipKey { x },
+            (0xE000, _) if nn == 0xA1 => Instruction::SkipNotKey { x },
+            (0xF000, _) => match nn {
+                0x07 => Instruction::LoadDelay { x },
+                0x0A => Instruction::WaitKey { x },
+                0x15 => Instruction::SetDelay { x },
+                0x18 => Instruction::SetSound { x },
+                0x1E => Instruction::AddI { x },
+                0x29 => Instruction::LoadFont { x },
+                0x33 => Instruction::Bcd { x },
+                0x55 => Instruction::Store { x },
+                0x65 => Instruction::Restore { x },
+                _ => Instruction::Unknown(opcode),
+            },
+            _ => Instruction::Unknown(opcode),
+        }
+    }
+
+    /// Decodes the instruction stored at `address`, see [`opcode_at`].
+    pub fn at(memory: &[u8], address: u16) -> Instruction {
+        Instruction::decode(opcode_at(memory, address))
+    }
+
+    /// How many bytes of memory, starting at `I`, the instruction reads or writes.
+    pub fn i_bytes(&self) -> u16 {
+        match *self {
+            Instruction::Draw { n, .. } => n as u16,
+            Instruction::Bcd { .. } => 3,
+            Instruction::Store { x } | Instruction::Restore { x } => x as u16 + 1,
+            _ => 0,
+        }
+    }
+}
+
+/// The big-endian opcode stored at `address`, reading past the end of
+/// `memory` as zeros.
+pub fn opcode_at(memory: &[u8], address: u16) -> u16 {
+    let byte = |address: u16| memory.get(address as usize).copied().unwrap_or(0) as u16;
+    (byte(address) << 8) | byte(address.wrapping_add(1))
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Instruction::Sys(nnn) => write!(f, "SYS {:03X}", nnn),
+            Instruction::Clear => write!(f, "CLS"),
+            Instruction::Return => write!(f, "RET"),
+            Instruction::Jump(nnn) => write!(f, "JP {:03X}", nnn),
+            Instruction::Call(nnn) => write!(f, "CALL {:03X}", nnn),
+            Instruction::SkipEqual { x, nn } => write!(f, "SE V{:X}, {:02X}", x, nn),
+            Instruction::SkipNotEqual { x, nn } => write!(f, "SNE V{:X}, {:02X}", x, nn),
+            Instruction::SkipEqualRegister { x, y } => write!(f, "SE V{:X}, V{:X}", x, y),
+            Instruction::Load { x, nn } => write!(f, "LD V{:X}, {:02X}", x, nn),
+            Instruction::Add { x, nn } => write!(f, "ADD V{:X}, {:02X}", x, nn),
+            Instruction::Move { x, y } => write!(f, "LD V{:X}, V{:X}", x, y),
+            Instruction::Or { x, y } => write!(f, "OR V{:X}, V{:X}", x, y),
+            Instruction::And { x, y } => write!(f, "AND V{:X}, V{:X}", x, y),
+            Instruction::Xor { x, y } => write!(f, "XOR V{:X}, V{:X}", x, y),
+            Instruction::AddRegister { x, y } => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Instruction::Sub { x, y } => write!(f, "SUB V{:X}, V{:X}", x, y),
+            Instruction::ShiftRight { x, y } => write!(f, "SHR V{:X}, V{:X}", x, y),
+            Instruction::SubReverse { x, y } => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            Instruction::ShiftLeft { x, y } => write!(f, "SHL V{:X}, V{:X}", x, y),
+            Instruction::SkipNotEqualRegister { x, y } => write!(f, "SNE V{:X}, V{:X}", x, y),
+            Instruction::LoadI(nnn) => write!(f, "LD I, {:03X}", nnn),
+            Instruction::JumpOffset(nnn) => write!(f, "JP V0, {:03X}", nnn),
+            Instruction::Random { x, nn } => write!(f, "RND V{:X}, {:02X}", x, nn),
+            Instruction::Draw { x, y, n } => write!(f, "DRW V{:X}, V{:X}, {:X}", x, y, n),
+            Instruction::SkipKey { x } => write!(f, "SKP V{:X}", x),
+            Instruction::SkipNotKey { x } => write!(f, "SKNP V{:X}", x),
+            Instruction::LoadDelay { x } => write!(f, "LD V{:X}, DT", x),
+            Instruction::WaitKey { x } => write!(f, "LD V{:X}, K", x),
+            Instruction::SetDelay { x } => write!(f, "LD DT, V{:X}", x),
+            Instruction::SetSound { x } => write!(f, "LD ST, V{:X}", x),
+            Instruction::AddI { x } => write!(f, "ADD I, V{:X}", x),
+            Instruction::LoadFont { x } => write!(f, "LD F, V{:X}", x),
+            Instruction::Bcd { x } => write!(f, "LD B, V{:X}", x),
+            Instruction::Store { x } => write!(f, "LD [I], V{:X}", x),
+            Instruction::Restore { x } => write!(f, "LD V{:X}, [I]", x),
+            Instruction::Unknown(opcode) => write!(f, "DW {:04X}", opcode),
+        }
+    }
+}
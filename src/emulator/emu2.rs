@@ -1,6 +1,7 @@
 use std::{
-    fs::File,
-    io::{self, Read},
+    io,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
 };
 
 use log::{debug, error};
@@ -9,123 +10,525 @@ use winit::{
     dpi::LogicalSize,
     event::{Event, KeyEvent, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
-    keyboard::Key,
+    keyboard::{Key, NamedKey},
     window::WindowBuilder,
 };
 
 use super::{
-    cpu::{Cpu, SCREEN_HEIGHT, SCREEN_WIDTH},
+    cheats::CheatList,
+    clock::FrameClock,
+    console::CheatConsole,
+    cpu::{SCREEN_HEIGHT, SCREEN_WIDTH},
+    debugger::DebugView,
+    frontend::{Colors, Control, Frame, FrontendResult, InputSource, NullFrontend, VideoSink},
+    keymap,
+    library::RomLibrary,
+    machine::Machine,
+    menu::{Menu, MenuAction},
     options::EmulatorOptions,
+    overlay::{self, Overlay},
+    palette::Palette,
+    rom, screenshot,
 };
 
 pub struct Emu2 {
     options: EmulatorOptions,
-    rom: Option<Vec<u8>>,
+    game: Option<Game>,
+    menu: Option<Menu>,
+    console: Option<CheatConsole>,
+}
+
+/// The program being played, and where it was loaded from.
+struct Game {
+    machine: Machine,
+    path: String,
+    /// The ROM inside the ZIP archive at `path`.
+    entry: Option<String>,
 }
 
 impl Emu2 {
     pub fn new(options: EmulatorOptions) -> Self {
-        Self { options, rom: None }
+        Self {
+            options,
+            game: None,
+            menu: None,
+            console: None,
+        }
     }
 
+    /// Loads the ROM at `path`, asking on stdin which one to load from a
+    /// ZIP archive or directory holding several, before the window opens.
     pub fn load_rom(&mut self, path: &str) -> Result<(), io::Error> {
-        let program_data = Emu2::load_rom_file(path)?;
-        self.rom = Some(program_data);
+        let entry = if rom::is_collection(path) {
+            let roms = rom::collection_roms(path)?;
+            if roms.len() > 1 {
+                Some(rom::choose_rom(&roms)?)
+            } else {
+                None
+            }
+        } else {
+            None
+        };
+        self.game = Some(Emu2::launch(&self.options, path, entry.as_deref())?);
         Ok(())
     }
 
-    fn load_rom_file(path: &str) -> io::Result<Vec<u8>> {
-        let mut file = File::open(path)?;
-        let mut buffer = vec![];
-        let bytes_read = file.read_to_end(&mut buffer)?;
-
-        println!("Loaded '{}' ({} bytes read)", path, bytes_read);
+    /// Shows a menu listing `library` when no ROM was loaded, and when the
+    /// game is exited with Escape.
+    pub fn set_library(&mut self, library: RomLibrary) {
+        self.menu = Some(Menu::new(library));
+    }
 
-        Ok(buffer)
+    /// Reads memory editing and cheat commands from stdin while playing,
+    /// see [`CheatConsole`].
+    pub fn enable_cheat_console(&mut self) {
+        self.console = Some(CheatConsole::start());
     }
 
     pub fn run(self) {
-        let mut cpu = Cpu::new();
-        if let Some(rom) = self.rom {
-            cpu.load_rom(&rom);
-        } else {
+        let options = self.options;
+        let mut game = self.game;
+        let mut menu = self.menu;
+        let mut console = self.console;
+        if game.is_none() && menu.is_none() {
             panic!("No rom was loaded!");
         }
 
         let event_loop = EventLoop::new().unwrap();
-        event_loop.set_control_flow(ControlFlow::Poll);
         let window = {
             let size = LogicalSize::new(
-                (SCREEN_WIDTH as f64) * (self.options.scaling as f64),
-                (SCREEN_HEIGHT as f64) * (self.options.scaling as f64),
+                (SCREEN_WIDTH as f64) * (options.scaling as f64),
+                (SCREEN_HEIGHT as f64) * (options.scaling as f64),
             );
             WindowBuilder::new()
-                .with_title("Hello Pixels")
+                .with_title(Emu2::title(game.as_ref()))
                 .with_inner_size(size)
                 .with_min_inner_size(size)
                 .build(&event_loop)
                 .unwrap()
         };
 
-        let mut screen_renderer = {
+        let mut video = {
             let window_size = window.inner_size();
             let surface_texture =
                 SurfaceTexture::new(window_size.width, window_size.height, &window);
-            Pixels::new(SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32, surface_texture).unwrap()
-            // todo: handle error
+            PixelsVideo {
+                pixels: Pixels::new(
+                    overlay::WIDTH as u32,
+                    overlay::HEIGHT as u32,
+                    surface_texture,
+                )
+                .unwrap(), // todo: handle error
+                palette: options.palette,
+                overlay: Overlay::default(),
+            }
         };
+        let mut input = WinitInput::default();
+        let mut audio = NullFrontend;
 
-        let mut frame_count_timer = 0;
-        let res = event_loop.run(|event, event_handler| {
-            match event {
-                Event::WindowEvent { event, .. } => match event {
-                    WindowEvent::CloseRequested => event_handler.exit(),
-                    WindowEvent::KeyboardInput { mut event, .. } => {
-                        Emu2::input(&mut event, &mut cpu)
+        let mut frame_clock = FrameClock::new();
+        let res = event_loop.run(|event, event_handler| match event {
+            Event::WindowEvent { event, .. } => match event {
+                WindowEvent::CloseRequested => event_handler.exit(),
+                WindowEvent::KeyboardInput { event, .. } => {
+                    let pressed = event.state.is_pressed() && !event.repeat;
+                    let overlay = &mut video.overlay;
+                    if pressed {
+                        match event.logical_key {
+                            Key::Named(NamedKey::F1) => {
+                                overlay.show_key_hints = !overlay.show_key_hints
+                            }
+                            Key::Named(NamedKey::F2) => overlay.show_stats = !overlay.show_stats,
+                            _ => {}
+                        }
+                        window.request_redraw();
+                    }
+
+                    match (&mut game, &mut menu) {
+                        (Some(current), _) if pressed => match event.logical_key {
+                            Key::Named(NamedKey::Escape) if menu.is_some() => {
+                                Emu2::stop_recording(&mut current.machine, overlay);
+                                Emu2::write_reports(&current.machine);
+                                game = None;
+                                overlay.paused = false;
+                                overlay.debug = None;
+                                window.set_title(&Emu2::title(None));
+                            }
+                            Key::Named(NamedKey::F3) => overlay.paused = !overlay.paused,
+                            Key::Named(NamedKey::F4) => {
+                                overlay.debug = match overlay.debug {
+                                    Some(_) => None,
+                                    None => Some(DebugView::capture(&current.machine.cpu)),
+                                }
+                            }
+                            Key::Named(NamedKey::F5) => {
+                                current.machine.reset();
+                                overlay.notify("Reset");
+                            }
+                            Key::Named(NamedKey::F6) => {
+                                let entry = current.entry.as_deref();
+                                match Emu2::launch(&options, &current.path, entry) {
+                                    Ok(reloaded) => {
+                                        Emu2::stop_recording(&mut current.machine, overlay);
+                                        *current = reloaded;
+                                        overlay.notify("Reloaded");
+                                    }
+                                    Err(err) => {
+                                        error!("Cannot reload rom: {}", err);
+                                        overlay.notify("Cannot reload rom");
+                                    }
+                                }
+                            }
+                            Key::Named(NamedKey::F9) => {
+                                Emu2::toggle_recording(&mut current.machine, overlay)
+                            }
+                            Key::Named(NamedKey::F12) => {
+                                Emu2::screenshot(&current.machine, overlay)
+                            }
+                            _ => {}
+                        },
+                        (None, Some(menu)) if pressed => {
+                            let Some(action) = Emu2::menu_action(&event) else {
+                                return;
+                            };
+                            let Some(entry) = menu.navigate(action).cloned() else {
+                                window.request_redraw();
+                                return;
+                            };
+                            let path = entry.path.to_string_lossy();
+                            // Archives of several ROMs open as a list of their own.
+                            if entry.entry.is_none() && rom::is_collection(&path) {
+                                match rom::collection_roms(&path) {
+                                    Ok(roms) if roms.len() > 1 => {
+                                        menu.open(RomLibrary::archive(&entry.path, roms));
+                                        window.request_redraw();
+                                        return;
+                                    }
+                                    Ok(_) => {}
+                                    Err(err) => {
+                                        error!("Cannot open rom: {}", err);
+                                        overlay.notify("Cannot open rom");
+                                        return;
+                                    }
+                                }
+                            }
+                            match Emu2::launch(&options, &path, entry.entry.as_deref()) {
+                                Ok(launched) => {
+                                    game = Some(launched);
+                                    window.set_title(&Emu2::title(game.as_ref()));
+                                    input = WinitInput::default();
+                                }
+                                Err(err) => {
+                                    error!("Cannot open rom: {}", err);
+                                    overlay.notify("Cannot open rom");
+                                }
+                            }
+                            return;
+                        }
+                        _ => {}
                     }
-                    WindowEvent::RedrawRequested => {
-                        if let Err(error) = Emu2::draw(&mut screen_renderer, &mut cpu) {
-                            println!("error: {}", error);
-                            event_handler.exit();
+                    if game.is_some() {
+                        input.handle(&event)
+                    }
+                }
+                WindowEvent::RedrawRequested => {
+                    let res = match (&mut game, &menu) {
+                        (Some(current), _) => {
+                            video.palette = current.machine.options().palette;
+                            current.machine.present(&mut video)
+                        }
+                        (None, Some(menu)) => {
+                            video.palette = options.palette;
+                            video.present(&Frame::new(&menu.render(), SCREEN_WIDTH))
                         }
+                        (None, None) => Ok(()),
+                    };
+                    if let Err(error) = res {
+                        println!("error: {}", error);
+                        event_handler.exit();
                     }
-                    _ => {}
-                },
+                }
                 _ => {}
-            }
-
-            cpu.run_instruction();
-            frame_count_timer += 1;
-            if frame_count_timer > 30 {
-                cpu.tick_timers();
-            }
+            },
+            Event::AboutToWait => {
+                let frames = frame_clock.frames_due();
+                if let (Some(console), Some(current)) = (&mut console, &mut game) {
+                    console.poll(&mut current.machine);
+                }
+                let mut crashed = false;
+                match (&mut game, &mut menu) {
+                    // Frames still go by while paused, they are just not run.
+                    (Some(_), _) if video.overlay.paused => {}
+                    (Some(current), _) => {
+                        let seen = current.machine.cpu.last_violation.clone();
+                        match current.machine.update(frames, &mut input, &mut audio) {
+                            Ok((redraw, Control::Continue)) => {
+                                if redraw {
+                                    window.request_redraw();
+                                }
+                                // Stops on memory violations for a look in the debugger.
+                                let violation = &current.machine.cpu.last_violation;
+                                if let Some(error) =
+                                    violation.as_ref().filter(|_| *violation != seen)
+                                {
+                                    video.overlay.notify(error.to_string());
+                                    video.overlay.paused = true;
+                                    video.overlay.debug =
+                                        Some(DebugView::capture(&current.machine.cpu));
+                                    window.request_redraw();
+                                }
+                            }
+                            Ok((_, Control::Quit)) => event_handler.exit(),
+                            // Back to the menu, if there is one to go back to.
+                            Err(error) if menu.is_some() => {
+                                error!("{}", error);
+                                Emu2::stop_recording(&mut current.machine, &mut video.overlay);
+                                Emu2::write_reports(&current.machine);
+                                video.overlay.notify(error.to_string());
+                                crashed = true;
+                            }
+                            Err(error) => {
+                                error!("{}", error);
+                                event_handler.exit();
+                            }
+                        }
+                    }
+                    (None, Some(menu)) => {
+                        let mut redraw = false;
+                        for _ in 0..frames {
+                            redraw |= menu.tick();
+                        }
+                        if redraw {
+                            window.request_redraw();
+                        }
+                    }
+                    (None, None) => event_handler.exit(),
+                }
+                if crashed {
+                    game = None;
+                    video.overlay.paused = false;
+                    video.overlay.debug = None;
+                    window.set_title(&Emu2::title(None));
+                    window.request_redraw();
+                }
 
-            if cpu.draw_flag {
-                window.request_redraw();
+                let (frame_count, instruction_count) = game
+                    .as_ref()
+                    .map(|current| {
+                        let machine = &current.machine;
+                        (machine.frame_count(), machine.instruction_count())
+                    })
+                    .unwrap_or_default();
+                if video.overlay.tick(frame_count, instruction_count) {
+                    window.request_redraw();
+                }
+                if let (Some(current), Some(_)) = (&game, &video.overlay.debug) {
+                    video.overlay.debug = Some(DebugView::capture(&current.machine.cpu));
+                    window.request_redraw();
+                }
+                event_handler.set_control_flow(ControlFlow::WaitUntil(frame_clock.next_frame()));
             }
+            _ => {}
         });
 
         if let Err(error) = res {
             error!("{}", error);
         }
+        if let Some(current) = &mut game {
+            Emu2::stop_recording(&mut current.machine, &mut video.overlay);
+            Emu2::write_reports(&current.machine);
+        }
     }
 
-    fn draw(screen_renderer: &mut Pixels, cpu: &mut Cpu) -> std::result::Result<(), pixels::Error> {
-        for (i, pixel) in screen_renderer.frame_mut().chunks_exact_mut(4).enumerate() {
-            let color = if cpu.screen[i] > 0 {
-                [0xFF, 0xFF, 0xFF, 0xFF]
-            } else {
-                [0x00, 0x00, 0x00, 0x00]
-            };
-            pixel.copy_from_slice(&color);
+    /// Loads the ROM at `path` into a new machine, on top of `options`.
+    /// `entry` picks the ROM of a ZIP archive, which may be left out when
+    /// it holds a single one: the window can't ask on stdin.
+    fn launch(
+        options: &EmulatorOptions,
+        path: &str,
+        entry: Option<&str>,
+    ) -> Result<Game, io::Error> {
+        let entry = match entry {
+            Some(entry) => Some(entry.to_string()),
+            None if rom::is_collection(path) => {
+                let mut roms = rom::collection_roms(path)?;
+                if roms.len() > 1 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("'{}' holds several ROMs, pick one from the menu", path),
+                    ));
+                }
+                roms.pop()
+            }
+            None => None,
+        };
+        let mut options = options.clone();
+        let program_data = rom::load_rom(path, entry.as_deref(), &mut options)?;
+
+        let mut machine = Machine::new(options);
+        machine.load_rom(program_data);
+
+        let cheats_path = CheatList::path_for(machine.rom());
+        if cheats_path.exists() {
+            match CheatList::load(&cheats_path) {
+                Ok(cheats) => {
+                    println!("Loaded {} cheats", cheats.cheats.len());
+                    machine.cheats = cheats;
+                }
+                Err(err) => error!("Cannot load cheats: {}", err),
+            }
+        }
+        Ok(Game {
+            machine,
+            path: path.to_string(),
+            entry,
+        })
+    }
+
+    fn title(game: Option<&Game>) -> String {
+        match game.and_then(|game| Path::new(&game.path).file_stem()) {
+            Some(name) => format!("chipo - {}", name.to_string_lossy()),
+            None => String::from("chipo"),
         }
+    }
 
-        screen_renderer.render()
+    /// Arrow keys, Page Up/Down, Enter and Escape or Backspace to go back,
+    /// or 5/8 to move, 6 to launch and 4 to go back on the keypad.
+    fn menu_action(event: &KeyEvent) -> Option<MenuAction> {
+        match &event.logical_key {
+            Key::Named(NamedKey::ArrowUp) => Some(MenuAction::Up),
+            Key::Named(NamedKey::ArrowDown) => Some(MenuAction::Down),
+            Key::Named(NamedKey::PageUp) => Some(MenuAction::PageUp),
+            Key::Named(NamedKey::PageDown) => Some(MenuAction::PageDown),
+            Key::Named(NamedKey::Enter) => Some(MenuAction::Launch),
+            Key::Named(NamedKey::Escape | NamedKey::Backspace) => Some(MenuAction::Back),
+            Key::Character(keystr) => match keymap::get_chip8_key_code(keystr)? {
+                0x5 => Some(MenuAction::Up),
+                0x8 => Some(MenuAction::Down),
+                0x6 => Some(MenuAction::Launch),
+                0x4 => Some(MenuAction::Back),
+                _ => None,
+            },
+            _ => None,
+        }
     }
 
-    fn input(input: &mut KeyEvent, cpu: &mut Cpu) {
+    fn write_reports(machine: &Machine) {
+        if let Err(error) = machine.write_reports() {
+            error!("Cannot save reports: {}", error);
+        }
+    }
+
+    fn stop_recording(machine: &mut Machine, overlay: &mut Overlay) {
+        if !machine.is_recording() {
+            return;
+        }
+        match machine.stop_recording() {
+            Ok(()) => overlay.notify("Recording saved"),
+            Err(error) => {
+                error!("Cannot save recording: {}", error);
+                overlay.notify("Cannot save recording");
+            }
+        }
+    }
+
+    /// `chipo-<timestamp>.<extension>`, in the current directory.
+    fn timestamped_path(extension: &str) -> PathBuf {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or_default();
+        PathBuf::from(format!("chipo-{}.{}", timestamp, extension))
+    }
+
+    /// Starts recording to `chipo-<timestamp>.gif`, or stops the current recording.
+    fn toggle_recording(machine: &mut Machine, overlay: &mut Overlay) {
+        if machine.is_recording() {
+            Emu2::stop_recording(machine, overlay);
+            return;
+        }
+
+        match machine.start_recording(&Emu2::timestamped_path("gif")) {
+            Ok(()) => overlay.notify("Recording"),
+            Err(error) => {
+                error!("Cannot record: {}", error);
+                overlay.notify("Cannot record");
+            }
+        }
+    }
+
+    /// Saves what is on the window to `chipo-<timestamp>.png`, at the window scaling.
+    fn screenshot(machine: &Machine, overlay: &mut Overlay) {
+        let path = Emu2::timestamped_path("png");
+        let options = machine.options();
+
+        match screenshot::save_png(
+            &path,
+            &machine.frame(),
+            &options.palette,
+            options.scaling as u32,
+        ) {
+            Ok(()) => overlay.notify(format!("Saved {}", path.display())),
+            Err(error) => {
+                error!("Cannot save screenshot: {}", error);
+                overlay.notify("Cannot save screenshot");
+            }
+        }
+    }
+}
+
+/// Draws frames into the `pixels` surface of the window, with the overlay on top.
+/// The surface is [`overlay::OVERLAY_SCALE`] times the CHIP-8 resolution, so that the
+/// overlay text can be finer than the CHIP-8 pixels.
+struct PixelsVideo<'win> {
+    pixels: Pixels<'win>,
+    palette: Palette,
+    overlay: Overlay,
+}
+
+impl VideoSink for PixelsVideo<'_> {
+    fn present(&mut self, frame: &Frame) -> FrontendResult {
+        // Larger screens are drawn with smaller pixels in the same window.
+        // MegaChip pixels are square, so its 4:3 screen is letterboxed.
+        let (width, left) = match frame.colors {
+            Some(Colors::Pixels(_)) => {
+                let width = overlay::HEIGHT * frame.width / frame.height;
+                (width, (overlay::WIDTH - width) / 2)
+            }
+            _ => (overlay::WIDTH, 0),
+        };
+
+        let surface = self.pixels.frame_mut();
+        for (index, pixel) in surface.chunks_exact_mut(4).enumerate() {
+            let (column, row) = (index % overlay::WIDTH, index / overlay::WIDTH);
+            if !(left..left + width).contains(&column) {
+                pixel.copy_from_slice(&[0, 0, 0, 0xFF]);
+                continue;
+            }
+            let x = (column - left) * frame.width / width;
+            let y = row * frame.height / overlay::HEIGHT;
+            pixel.copy_from_slice(&frame.color(x, y, &self.palette));
+        }
+        self.overlay.draw(surface);
+
+        Ok(self.pixels.render()?)
+    }
+}
+
+/// Keeps track of the keypads from winit keyboard events.
+#[derive(Default)]
+struct WinitInput {
+    keys: [bool; 16],
+    keys2: [bool; 16],
+}
+
+impl WinitInput {
+    fn handle(&mut self, input: &KeyEvent) {
         if let Key::Character(keystr) = &input.logical_key {
-            if let Some(chip8_key) = Emu2::get_chip8_key_code(&keystr) {
+            if let Some(chip8_key) = keymap::get_second_keypad_key_code(keystr) {
+                self.keys2[chip8_key as usize] = input.state.is_pressed();
+            }
+            if let Some(chip8_key) = keymap::get_chip8_key_code(keystr) {
                 debug!(
                     "keyboard event: {} -> {}",
                     &keystr,
@@ -135,30 +538,19 @@ impl Emu2 {
                         "released"
                     }
                 );
-                cpu.keys[chip8_key as usize] = input.state.is_pressed();
+                self.keys[chip8_key as usize] = input.state.is_pressed();
             }
         }
     }
+}
 
-    fn get_chip8_key_code(key: &str) -> Option<u8> {
-        match key {
-            "1" => Some(0x1),
-            "2" => Some(0x2),
-            "3" => Some(0x3),
-            "4" => Some(0xC),
-            "q" => Some(0x4),
-            "w" => Some(0x5),
-            "e" => Some(0x6),
-            "r" => Some(0xD),
-            "a" => Some(0x7),
-            "s" => Some(0x8),
-            "d" => Some(0x9),
-            "f" => Some(0xE),
-            "z" => Some(0xA),
-            "x" => Some(0x0),
-            "c" => Some(0xB),
-            "v" => Some(0xF),
-            _ => None,
-        }
+impl InputSource for WinitInput {
+    fn poll(&mut self, keys: &mut [bool; 16]) -> Control {
+        keys.copy_from_slice(&self.keys);
+        Control::Continue
+    }
+
+    fn poll_second_keypad(&mut self, keys: &mut [bool; 16]) {
+        keys.copy_from_slice(&self.keys2);
     }
 }
@@ -0,0 +1,69 @@
+use std::{error::Error, fmt};
+
+/// Errors raised while running a program.
+/// These would crash or hang the original interpreter.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CpuError {
+    /// The opcode at `address` isn't part of the instruction set.
+    UnknownOpcode { opcode: u16, address: u16 },
+
+    /// `00EE` was executed at `address` with nothing on the call stack.
+    StackUnderflow { address: u16 },
+
+    /// `2NNN` was executed at `address` with the call stack full.
+    StackOverflow { address: u16 },
+
+    /// The instruction at `address` accessed `target`, past the end of memory.
+    AddressOutOfRange { address: u16, target: u32 },
+
+    /// The instruction at `address` wrote to `target`, below the program start.
+    ProtectedWrite { address: u16, target: u16 },
+
+    /// The bytes at `address` were executed after being used as data.
+    DataExecuted { address: u16 },
+
+    /// The machine code called by `0NNN` at `address` didn't return in time.
+    MachineCodeTimeout { address: u16 },
+}
+
+impl fmt::Display for CpuError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CpuError::UnknownOpcode { opcode, address } => {
+                write!(f, "unrecognized opcode {:#06X} at {:#05X}", opcode, address)
+            }
+            CpuError::StackUnderflow { address } => {
+                write!(
+                    f,
+                    "tried to return at {:#05X} but the stack is empty",
+                    address
+                )
+            }
+            CpuError::StackOverflow { address } => {
+                write!(f, "tried to call at {:#05X} but the stack is full", address)
+            }
+            CpuError::AddressOutOfRange { address, target } => {
+                write!(
+                    f,
+                    "accessed {:#X} past the end of memory at {:#05X}",
+                    target, address
+                )
+            }
+            CpuError::ProtectedWrite { address, target } => {
+                write!(
+                    f,
+                    "wrote to the interpreter area at {:#05X} from {:#05X}",
+                    target, address
+                )
+            }
+            CpuError::DataExecuted { address } => {
+                write!(f, "executed data at {:#05X}", address)
+            }
+            CpuError::MachineCodeTimeout { address } => {
+                write!(f, "machine code called at {:#05X} never returned", address)
+            }
+        }
+    }
+}
+
+impl Error for CpuError {}
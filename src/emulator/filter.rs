@@ -0,0 +1,128 @@
+use super::cpu::{SCREEN_HEIGHT, SCREEN_WIDTH};
+
+/// How the screen is post-processed before being presented.
+///
+/// CHIP-8 games erase and redraw sprites with XOR, so a sprite that moves
+/// is "off" for a frame or two and flickers. These filters hide that.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FilterMode {
+    /// Show `Cpu::screen` as is.
+    None,
+    /// Lit pixels fade out over `frames` frames, like a CRT phosphor.
+    Phosphor { frames: u8 },
+    /// Mixes the previous frame into the current one.
+    /// `weight` is how much (0-100%) of the previous frame is kept.
+    Blend { weight: u8 },
+}
+
+impl FilterMode {
+    /// Parses `none`, `phosphor[:frames]` or `blend[:weight]`.
+    pub fn parse(value: &str) -> Option<FilterMode> {
+        let (name, strength) = match value.split_once(':') {
+            Some((name, strength)) => (name, Some(strength.parse::<u8>().ok()?)),
+            None => (value, None),
+        };
+
+        match name {
+            "none" => Some(FilterMode::None),
+            "phosphor" => Some(FilterMode::Phosphor {
+                frames: strength.unwrap_or(4).max(1),
+            }),
+            "blend" => Some(FilterMode::Blend {
+                weight: strength.unwrap_or(50).min(100),
+            }),
+            _ => None,
+        }
+    }
+}
+
+pub struct DisplayFilter {
+    mode: FilterMode,
+
+    /// Intensity (0-255) of each pixel as it was last presented.
+    intensity: Vec<u8>,
+
+    /// The screen as it was on the previous frame, used by `Blend`.
+    previous: Vec<u8>,
+
+    /// Width of the screen the intensities are for.
+    width: usize,
+}
+
+impl DisplayFilter {
+    pub fn new(mode: FilterMode) -> Self {
+        Self {
+            mode,
+            intensity: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT],
+            previous: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT],
+            width: SCREEN_WIDTH,
+        }
+    }
+
+    /// Feeds one frame of the screen, `width` pixels wide, through the filter.
+    /// Returns the intensity of every pixel, from 0 (off) to 255 (fully lit).
+    /// Nothing fades across a change of resolution.
+    pub fn apply(&mut self, screen: &[u8], width: usize) -> &[u8] {
+        if screen.len() != self.intensity.len() || width != self.width {
+            self.intensity = vec![0; screen.len()];
+            self.previous = vec![0; screen.len()];
+            self.width = width;
+        }
+
+        match self.mode {
+            FilterMode::None => {
+                for (out, pixel) in self.intensity.iter_mut().zip(screen) {
+                    *out = if *pixel > 0 { 0xFF } else { 0x00 };
+                }
+            }
+            FilterMode::Phosphor { frames } => {
+                let decay = (0xFF / frames as u16) as u8;
+                for (out, pixel) in self.intensity.iter_mut().zip(screen) {
+                    *out = if *pixel > 0 {
+                        0xFF
+                    } else {
+                        out.saturating_sub(decay)
+                    };
+                }
+            }
+            FilterMode::Blend { weight } => {
+                let kept = (0xFF * weight as u16 / 100) as u8;
+                for ((out, pixel), old) in self.intensity.iter_mut().zip(screen).zip(&self.previous)
+                {
+                    *out = if *pixel > 0 {
+                        0xFF
+                    } else if *old > 0 {
+                        kept
+                    } else {
+                        0x00
+                    };
+                }
+            }
+        }
+
+        self.previous.copy_from_slice(screen);
+        &self.intensity
+    }
+
+    /// The intensities returned by the last call to [`DisplayFilter::apply`].
+    pub fn current(&self) -> &[u8] {
+        &self.intensity
+    }
+
+    /// The width of [`DisplayFilter::current`].
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Returns `true` while some pixel is still fading out, meaning the
+    /// screen has to be presented again even if the CPU did not draw.
+    pub fn is_fading(&self) -> bool {
+        match self.mode {
+            FilterMode::None => false,
+            FilterMode::Phosphor { .. } | FilterMode::Blend { .. } => self
+                .intensity
+                .iter()
+                .any(|intensity| *intensity > 0 && *intensity < 0xFF),
+        }
+    }
+}
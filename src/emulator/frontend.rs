@@ -0,0 +1,102 @@
+use std::error::Error;
+
+use super::{chip8x::ColorZones, palette::Palette};
+
+pub type FrontendResult = Result<(), Box<dyn Error>>;
+
+/// The screen as presented, after the display filter was applied.
+#[derive(Clone, Copy)]
+pub struct Frame<'a> {
+    /// One value per pixel of `Cpu::screen`, row by row, from 0 (off) to
+    /// 255 (fully lit).
+    pub intensities: &'a [u8],
+    pub width: usize,
+    pub height: usize,
+    /// Colors used instead of the palette.
+    pub colors: Option<Colors<'a>>,
+}
+
+/// Where the colors of a frame come from, when not from the palette.
+#[derive(Clone, Copy)]
+pub enum Colors<'a> {
+    /// The CHIP-8X color zones, over the 1-bit screen.
+    Zones(&'a ColorZones),
+    /// The MegaChip screen, one RGBA color per pixel. The intensities are
+    /// ignored.
+    Pixels(&'a [[u8; 4]]),
+}
+
+impl<'a> Frame<'a> {
+    /// A monochrome frame `width` pixels wide.
+    pub fn new(intensities: &'a [u8], width: usize) -> Self {
+        Self {
+            intensities,
+            width,
+            height: intensities.len() / width,
+            colors: None,
+        }
+    }
+
+    /// Color of pixel (`x`, `y`), from `palette` or the frame [`Colors`].
+    pub fn color(&self, x: usize, y: usize, palette: &Palette) -> [u8; 4] {
+        let index = y * self.width + x;
+        match self.colors {
+            Some(Colors::Zones(zones)) => zones.palette_at(x, y).color(self.intensities[index]),
+            Some(Colors::Pixels(pixels)) => pixels[index],
+            None => palette.color(self.intensities[index]),
+        }
+    }
+}
+
+/// Where frames end up: a window, a terminal, a file...
+pub trait VideoSink {
+    /// Shows one frame.
+    fn present(&mut self, frame: &Frame) -> FrontendResult;
+}
+
+/// Plays the CHIP-8 buzzer.
+pub trait AudioSink {
+    /// Called once per frame; `playing` is `true` while the sound timer is running.
+    fn set_tone(&mut self, playing: bool);
+
+    /// Plays MegaChip sampled sound, 16-bit at [`super::recorder::SAMPLE_RATE`],
+    /// as many samples as the frames that were run.
+    fn play_samples(&mut self, _samples: &[i16]) {}
+}
+
+/// What the frontend wants the machine to do after polling its input.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Control {
+    Continue,
+    Quit,
+}
+
+/// Reports the state of the 16 keys of the keypad.
+pub trait InputSource {
+    /// Updates `keys` (`true` = pressed) with what happened since the last poll.
+    fn poll(&mut self, keys: &mut [bool; 16]) -> Control;
+
+    /// Updates the second keypad of CHIP-8X, called after [`InputSource::poll`].
+    fn poll_second_keypad(&mut self, _keys: &mut [bool; 16]) {}
+}
+
+/// A frontend with no screen, speakers or keyboard.
+/// Useful to run the emulator headless or from tests.
+#[derive(Default)]
+pub struct NullFrontend;
+
+impl VideoSink for NullFrontend {
+    fn present(&mut self, _frame: &Frame) -> FrontendResult {
+        Ok(())
+    }
+}
+
+impl AudioSink for NullFrontend {
+    fn set_tone(&mut self, _playing: bool) {}
+}
+
+impl InputSource for NullFrontend {
+    fn poll(&mut self, _keys: &mut [bool; 16]) -> Control {
+        Control::Continue
+    }
+}
@@ -0,0 +1,50 @@
+use std::{error::Error, io, path::Path};
+
+use super::{
+    error::CpuError,
+    frontend::{Control, NullFrontend},
+    machine::Machine,
+    options::EmulatorOptions,
+    rom, screenshot,
+};
+
+/// Runs a program without any window or terminal, as fast as possible.
+pub struct Headless {
+    pub machine: Machine,
+}
+
+impl Headless {
+    pub fn new(options: EmulatorOptions) -> Self {
+        Self {
+            machine: Machine::new(options),
+        }
+    }
+
+    pub fn load_rom(&mut self, path: &str) -> Result<(), io::Error> {
+        let program_data = rom::load_rom(path, None, self.machine.options_mut())?;
+        self.machine.load_rom(program_data);
+        Ok(())
+    }
+
+    /// Runs `frames` frames, one after the other, with no input.
+    pub fn run(&mut self, frames: u32) -> Result<(), CpuError> {
+        let mut null = NullFrontend;
+        for _ in 0..frames {
+            let (_, control) = self.machine.update(1, &mut null, &mut NullFrontend)?;
+            if control == Control::Quit {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Saves the current screen as a PNG, using the palette from the options.
+    pub fn screenshot(&self, path: &Path, scale: u32) -> Result<(), Box<dyn Error>> {
+        screenshot::save_png(
+            path,
+            &self.machine.frame(),
+            &self.machine.options().palette,
+            scale,
+        )
+    }
+}
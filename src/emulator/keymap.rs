@@ -0,0 +1,62 @@
+/// Host keys laid out like the CHIP-8 keypad, row by row.
+pub const HOST_LAYOUT: [[&str; 4]; 4] = [
+    ["1", "2", "3", "4"],
+    ["q", "w", "e", "r"],
+    ["a", "s", "d", "f"],
+    ["z", "x", "c", "v"],
+];
+
+/// Maps a host key to the CHIP-8 keypad, following the usual layout:
+///
+/// ```text
+/// 1 2 3 4        1 2 3 C
+/// Q W E R   ->   4 5 6 D
+/// A S D F        7 8 9 E
+/// Z X C V        A 0 B F
+/// ```
+pub fn get_chip8_key_code(key: &str) -> Option<u8> {
+    match key {
+        "1" => Some(0x1),
+        "2" => Some(0x2),
+        "3" => Some(0x3),
+        "4" => Some(0xC),
+        "q" => Some(0x4),
+        "w" => Some(0x5),
+        "e" => Some(0x6),
+        "r" => Some(0xD),
+        "a" => Some(0x7),
+        "s" => Some(0x8),
+        "d" => Some(0x9),
+        "f" => Some(0xE),
+        "z" => Some(0xA),
+        "x" => Some(0x0),
+        "c" => Some(0xB),
+        "v" => Some(0xF),
+        _ => None,
+    }
+}
+
+/// Host keys for the second keypad of CHIP-8X, on the right of the keyboard.
+pub const SECOND_HOST_LAYOUT: [[&str; 4]; 4] = [
+    ["7", "8", "9", "0"],
+    ["u", "i", "o", "p"],
+    ["j", "k", "l", ";"],
+    ["m", ",", ".", "/"],
+];
+
+/// Maps a host key to the second keypad, laid out like the first one:
+///
+/// ```text
+/// 7 8 9 0        1 2 3 C
+/// U I O P   ->   4 5 6 D
+/// J K L ;        7 8 9 E
+/// M , . /        A 0 B F
+/// ```
+pub fn get_second_keypad_key_code(key: &str) -> Option<u8> {
+    SECOND_HOST_LAYOUT
+        .iter()
+        .zip(HOST_LAYOUT)
+        .flat_map(|(second, first)| second.iter().zip(first))
+        .find(|(second, _)| **second == key)
+        .and_then(|(_, first)| get_chip8_key_code(first))
+}
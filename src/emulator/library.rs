@@ -0,0 +1,111 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use log::warn;
+
+use super::platform::Platform;
+
+/// Directory the ROM browser lists when none is configured.
+pub const DEFAULT_ROM_DIR: &str = "roms";
+
+/// Optional file in the ROM directory mapping file names to titles, e.g.
+/// `{ "BRIX.ch8": "Brix", "pack.zip": "Demo pack" }`.
+pub const TITLES_FILE: &str = "titles.json";
+
+#[derive(Clone)]
+pub struct RomEntry {
+    pub path: PathBuf,
+    pub title: String,
+    /// The ROM inside the ZIP archive or directory at `path`, see
+    /// [`RomLibrary::archive`].
+    pub entry: Option<String>,
+}
+
+/// The ROMs of a directory, as listed by the ROM browser.
+pub struct RomLibrary {
+    pub dir: PathBuf,
+    pub entries: Vec<RomEntry>,
+}
+
+impl RomLibrary {
+    /// Lists every ROM, ZIP archive and Octo cartridge in `dir`, sorted by title.
+    pub fn scan(dir: &Path) -> io::Result<RomLibrary> {
+        let titles = RomLibrary::load_titles(dir);
+
+        let mut entries = vec![];
+        for dir_entry in fs::read_dir(dir)? {
+            let path = dir_entry?.path();
+            if !path.is_file() || !RomLibrary::is_rom(&path) {
+                continue;
+            }
+
+            let file_name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let title = titles
+                .get(&file_name)
+                .cloned()
+                .unwrap_or_else(|| RomLibrary::title(&path));
+            entries.push(RomEntry {
+                path,
+                title,
+                entry: None,
+            });
+        }
+        entries.sort_by_key(|entry| entry.title.to_lowercase());
+
+        Ok(RomLibrary {
+            dir: dir.to_path_buf(),
+            entries,
+        })
+    }
+
+    /// Lists the ROMs `names` inside the ZIP archive or directory at `path`,
+    /// sorted by title.
+    pub fn archive(path: &Path, names: Vec<String>) -> RomLibrary {
+        let mut entries: Vec<RomEntry> = names
+            .into_iter()
+            .map(|name| RomEntry {
+                path: path.to_path_buf(),
+                title: RomLibrary::title(Path::new(&name)),
+                entry: Some(name),
+            })
+            .collect();
+        entries.sort_by_key(|entry| entry.title.to_lowercase());
+
+        RomLibrary {
+            dir: path.to_path_buf(),
+            entries,
+        }
+    }
+
+    /// The file name without its extension, with spaces for `_` and `-`.
+    fn title(path: &Path) -> String {
+        path.file_stem()
+            .map(|stem| stem.to_string_lossy().replace(['_', '-'], " "))
+            .unwrap_or_default()
+    }
+
+    fn is_rom(path: &Path) -> bool {
+        let is_container = path.extension().is_some_and(|extension| {
+            extension.eq_ignore_ascii_case("zip") || extension.eq_ignore_ascii_case("gif")
+        });
+        is_container || Platform::from_path(path).is_some()
+    }
+
+    fn load_titles(dir: &Path) -> HashMap<String, String> {
+        let path = dir.join(TITLES_FILE);
+        let Ok(data) = fs::read(&path) else {
+            return HashMap::new();
+        };
+
+        serde_json::from_slice(&data).unwrap_or_else(|err| {
+            warn!("Ignoring '{}': {}", path.display(), err);
+            HashMap::new()
+        })
+    }
+}
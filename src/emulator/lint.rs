@@ -0,0 +1,434 @@
+use std::{collections::BTreeSet, fmt, io, io::Write};
+
+use super::{
+    analysis::Analysis,
+    disasm::{self, Instruction},
+    platform::Platform,
+    quirks::Quirks,
+};
+
+const PLATFORMS: [Platform; 3] = [Platform::Chip8, Platform::SuperChip, Platform::XoChip];
+
+/// Something in a ROM that may not run the same everywhere.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Lint {
+    UnknownOpcode,
+    /// An opcode added by a later platform.
+    Extension(Platform),
+    /// `0NNN`, which runs COSMAC VIP machine code.
+    MachineCode,
+    /// A jump or call to an address outside the ROM.
+    OutsideRom(u16),
+    /// `8XY6` or `8XYE` with X ≠ Y, see [`Quirks::shift_ignores_vy`].
+    ShiftQuirk,
+    /// `FX55` or `FX65` followed by a use of I, see [`Quirks::load_store_keeps_i`].
+    LoadStoreQuirk,
+    /// `BNNN` with a non-zero X, see [`Quirks::jump_uses_vx`].
+    JumpQuirk,
+    /// A sprite drawn from the interpreter area, where fonts are stored
+    /// differently by each interpreter.
+    FontAreaDraw(u16),
+    /// `FX55` or `FX33` writing over code.
+    SelfModifying,
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Lint::UnknownOpcode => write!(f, "unknown opcode"),
+            Lint::Extension(platform) => write!(f, "{} opcode", platform.name()),
+            Lint::MachineCode => write!(f, "calls machine code, only runs on a COSMAC VIP"),
+            Lint::OutsideRom(target) => write!(f, "goes to {:03X}, outside the ROM", target),
+            Lint::ShiftQuirk => write!(f, "result depends on the shift quirk"),
+            Lint::LoadStoreQuirk => {
+                write!(f, "I is used afterwards, depends on the load/store quirk")
+            }
+            Lint::JumpQuirk => write!(f, "target depends on the jump quirk"),
+            Lint::FontAreaDraw(i) => {
+                write!(f, "draws from {:03X}, in the interpreter's font area", i)
+            }
+            Lint::SelfModifying => write!(f, "writes over code"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Finding {
+    pub address: u16,
+    pub opcode: u16,
+    pub lint: Lint,
+}
+
+/// How a platform would run the ROM.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Verdict {
+    Compatible,
+    /// Runs, but behaves as described where the ROM relies on a quirk.
+    DependsOnQuirks(Vec<&'static str>),
+    Incompatible(String),
+}
+
+/// The findings of a static pass over the code reachable in a ROM, and what
+/// they mean for each platform.
+pub struct LintReport {
+    pub findings: Vec<Finding>,
+    pub platforms: Vec<(Platform, Verdict)>,
+    analysis: Analysis,
+}
+
+impl LintReport {
+    /// Lints `rom` as loaded at `load_address`.
+    pub fn lint(rom: &[u8], load_address: u16) -> LintReport {
+        let analysis = Analysis::analyze(rom, load_address);
+        let mut report = LintReport {
+            findings: vec![],
+            platforms: vec![],
+            analysis,
+        };
+        report.check_instructions();
+        report.check_font_draws();
+        report.check_self_modifications();
+        report.findings.sort_by_key(|finding| finding.address);
+        report.platforms = PLATFORMS
+            .iter()
+            .map(|platform| (*platform, report.verdict(*platform)))
+            .collect();
+        report
+    }
+
+    fn add(&mut self, address: u16, lint: Lint) {
+        self.findings.push(Finding {
+            address,
+            opcode: disasm::opcode_at(self.analysis.memory(), address),
+            lint,
+        });
+    }
+
+    fn check_instructions(&mut self) {
+        let rom = self.analysis.entry..self.analysis.memory().len() as u16;
+        let addresses: Vec<u16> = self
+            .analysis
+            .blocks
+            .values()
+            .flat_map(|block| (block.start..block.end).step_by(2))
+            .collect();
+
+        for address in addresses {
+            let opcode = disasm::opcode_at(self.analysis.memory(), address);
+            if let Some(platform) = LintReport::extension(opcode) {
+                self.add(address, Lint::Extension(platform));
+                continue;
+            }
+
+            match self.analysis.instruction(address) {
+                Instruction::Unknown(_) => self.add(address, Lint::UnknownOpcode),
+                Instruction::Sys(_) => self.add(address, Lint::MachineCode),
+                Instruction::Jump(target) | Instruction::Call(target) if !rom.contains(&target) => {
+                    self.add(address, Lint::OutsideRom(target))
+                }
+                Instruction::ShiftRight { x, y } | Instruction::ShiftLeft { x, y } if x != y => {
+                    self.add(address, Lint::ShiftQuirk)
+                }
+                Instruction::Store { .. } | Instruction::Restore { .. }
+                    if self.uses_i_after(address) =>
+                {
+                    self.add(address, Lint::LoadStoreQuirk)
+                }
+                Instruction::JumpOffset(nnn) => {
+                    if nnn & 0xF00 != 0 {
+                        self.add(address, Lint::JumpQuirk);
+                    }
+                    if !rom.contains(&nnn) {
+                        self.add(address, Lint::OutsideRom(nnn));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Opcodes of SUPER-CHIP and XO-CHIP, which the CHIP-8 decoder doesn't know
+    /// or treats as machine code calls.
+    fn extension(opcode: u16) -> Option<Platform> {
+        match opcode {
+            0x00C0..=0x00CF | 0x00FB..=0x00FF => Some(Platform::SuperChip),
+            0x00D0..=0x00DF | 0xF000 | 0xF002 => Some(Platform::XoChip),
+            _ if opcode & 0xF00F == 0xD000 => Some(Platform::SuperChip),
+            _ => match opcode & 0xF0FF {
+                0xF030 | 0xF075 | 0xF085 => Some(Platform::SuperChip),
+                0xF001 | 0xF03A => Some(Platform::XoChip),
+                _ if matches!(opcode & 0xF00F, 0x5002 | 0x5003) => Some(Platform::XoChip),
+                _ => None,
+            },
+        }
+    }
+
+    /// Whether I may be used after the `FX55`/`FX65` at `address`, before
+    /// being set again. Follows the flow, calls included.
+    fn uses_i_after(&self, address: u16) -> bool {
+        let mut visited = BTreeSet::new();
+        let mut pending = vec![address + 2];
+        while let Some(start) = pending.pop() {
+            if !visited.insert(start) {
+                continue;
+            }
+            let Some(block) = self.analysis.block_at(start) else {
+                continue;
+            };
+
+            let mut next_blocks = true;
+            for address in (start..block.end).step_by(2) {
+                match self.analysis.instruction(address) {
+                    Instruction::LoadI(_) | Instruction::LoadFont { .. } => {
+                        next_blocks = false;
+                        break;
+                    }
+                    instruction if instruction.i_bytes() > 0 => return true,
+                    Instruction::AddI { .. } => return true,
+                    _ => {}
+                }
+            }
+            if next_blocks {
+                pending.extend(block.successors.iter().map(|edge| edge.target));
+            }
+        }
+        false
+    }
+
+    /// Looks for sprites drawn with I set by `ANNN` below the program, where
+    /// the interpreter is.
+    fn check_font_draws(&mut self) {
+        let mut draws = vec![];
+        for block in self.analysis.blocks.values() {
+            let mut i = None;
+            for address in (block.start..block.end).step_by(2) {
+                match self.analysis.instruction(address) {
+                    Instruction::LoadI(value) => i = Some(value),
+                    Instruction::AddI { .. } | Instruction::LoadFont { .. } => i = None,
+                    Instruction::Draw { .. } => {
+                        if let Some(i) = i.filter(|i| *i < self.analysis.entry) {
+                            draws.push((address, i));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        for (address, i) in draws {
+            self.add(address, Lint::FontAreaDraw(i));
+        }
+    }
+
+    fn check_self_modifications(&mut self) {
+        let sites: Vec<u16> = self
+            .analysis
+            .self_modifications
+            .iter()
+            .map(|site| site.address)
+            .collect();
+        for address in sites {
+            self.add(address, Lint::SelfModifying);
+        }
+    }
+
+    fn verdict(&self, platform: Platform) -> Verdict {
+        let uses = |lint: &Lint| self.findings.iter().any(|finding| finding.lint == *lint);
+
+        if platform != Platform::Chip8 && uses(&Lint::MachineCode) {
+            return Verdict::Incompatible(String::from("calls COSMAC VIP machine code"));
+        }
+        let needs = |extension: Platform| match extension {
+            Platform::SuperChip => platform == Platform::Chip8,
+            Platform::XoChip => platform != Platform::XoChip,
+            _ => false,
+        };
+        for extension in [Platform::SuperChip, Platform::XoChip] {
+            if needs(extension) && uses(&Lint::Extension(extension)) {
+                return Verdict::Incompatible(format!("uses {} opcodes", extension.name()));
+            }
+        }
+
+        let quirks: Quirks = platform.quirks();
+        let mut behaviours = vec![];
+        if uses(&Lint::ShiftQuirk) {
+            behaviours.push(if quirks.shift_ignores_vy {
+                "8XY6/8XYE shift VX"
+            } else {
+                "8XY6/8XYE shift VY into VX"
+            });
+        }
+        if uses(&Lint::LoadStoreQuirk) {
+            behaviours.push(if quirks.load_store_keeps_i {
+                "FX55/FX65 leave I unchanged"
+            } else {
+                "FX55/FX65 move I past the registers"
+            });
+        }
+        if uses(&Lint::JumpQuirk) {
+            behaviours.push(if quirks.jump_uses_vx {
+                "BNNN jumps to XNN + VX"
+            } else {
+                "BNNN jumps to NNN + V0"
+            });
+        }
+        if behaviours.is_empty() {
+            Verdict::Compatible
+        } else {
+            Verdict::DependsOnQuirks(behaviours)
+        }
+    }
+
+    pub fn write(&self, out: &mut dyn Write) -> io::Result<()> {
+        if self.findings.is_empty() {
+            writeln!(out, "No problems found.")?;
+        }
+        for finding in &self.findings {
+            let instruction = Instruction::decode(finding.opcode).to_string();
+            writeln!(
+                out,
+                "{:04X}  {:04X}  {:16}  {}",
+                finding.address, finding.opcode, instruction, finding.lint
+            )?;
+        }
+
+        writeln!(out, "\nPlatforms:")?;
+        for (platform, verdict) in &self.platforms {
+            let verdict = match verdict {
+                Verdict::Compatible => String::from("likely compatible"),
+                Verdict::DependsOnQuirks(behaviours) => {
+                    format!("runs, but here {}", behaviours.join(", "))
+                }
+                Verdict::Incompatible(reason) => format!("not compatible, {}", reason),
+            };
+            writeln!(out, "  {:10}  {}", platform.name(), verdict)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lints(rom: &[u8], load_address: u16) -> Vec<(u16, Lint)> {
+        LintReport::lint(rom, load_address)
+            .findings
+            .into_iter()
+            .map(|finding| (finding.address, finding.lint))
+            .collect()
+    }
+
+    fn verdicts(rom: &[u8]) -> Vec<Verdict> {
+        LintReport::lint(rom, 0x200)
+            .platforms
+            .into_iter()
+            .map(|(_, verdict)| verdict)
+            .collect()
+    }
+
+    #[test]
+    fn unknown_opcodes() {
+        assert_eq!(lints(&[0xFF, 0xFF], 0x200), [(0x200, Lint::UnknownOpcode)]);
+    }
+
+    #[test]
+    fn extensions() {
+        // 00FF HIGH, F002 AUDIO
+        assert_eq!(
+            lints(&[0x00, 0xFF, 0xF0, 0x02], 0x200),
+            [
+                (0x200, Lint::Extension(Platform::SuperChip)),
+                (0x202, Lint::Extension(Platform::XoChip)),
+            ]
+        );
+    }
+
+    #[test]
+    fn machine_code() {
+        assert_eq!(lints(&[0x03, 0x00], 0x200), [(0x200, Lint::MachineCode)]);
+    }
+
+    #[test]
+    fn jumps_outside_the_rom_depend_on_the_load_address() {
+        // 1300 JP 300
+        assert_eq!(
+            lints(&[0x13, 0x00], 0x200),
+            [(0x200, Lint::OutsideRom(0x300))]
+        );
+        assert_eq!(lints(&[0x13, 0x00], 0x300), []);
+    }
+
+    #[test]
+    fn quirks() {
+        // 8016 SHR V0, V1    1202 JP 202
+        assert_eq!(
+            lints(&[0x80, 0x16, 0x12, 0x02], 0x200),
+            [(0x200, Lint::ShiftQuirk)]
+        );
+        // F055 LD [I], V0    F065 LD V0, [I]    1204 JP 204
+        assert_eq!(
+            lints(&[0xF0, 0x55, 0xF0, 0x65, 0x12, 0x04], 0x200),
+            [(0x200, Lint::LoadStoreQuirk)]
+        );
+        // B204 JP V0, 204    204: 1204 JP 204
+        assert_eq!(
+            lints(&[0xB2, 0x04, 0x00, 0x00, 0x12, 0x04], 0x200),
+            [(0x200, Lint::JumpQuirk)]
+        );
+    }
+
+    #[test]
+    fn font_area_draws_depend_on_the_load_address() {
+        // A300 LD I, 300    D015 DRW V0, V1, 5    1204 JP 204
+        assert_eq!(lints(&[0xA3, 0x00, 0xD0, 0x15, 0x12, 0x04], 0x200), []);
+        // The same at 600, where 300 is below the program.
+        assert_eq!(
+            lints(&[0xA3, 0x00, 0xD0, 0x15, 0x16, 0x04], 0x600),
+            [(0x602, Lint::FontAreaDraw(0x300))]
+        );
+    }
+
+    #[test]
+    fn self_modifying_code() {
+        // A202 LD I, 202    F055 LD [I], V0
+        assert_eq!(
+            lints(&[0xA2, 0x02, 0xF0, 0x55], 0x200),
+            [(0x202, Lint::SelfModifying)]
+        );
+    }
+
+    #[test]
+    fn verdicts_by_platform() {
+        let compatible = Verdict::Compatible;
+        // 1200 JP 200
+        assert_eq!(
+            verdicts(&[0x12, 0x00]),
+            [compatible.clone(), compatible.clone(), compatible.clone()]
+        );
+
+        let vip_only = Verdict::Incompatible(String::from("calls COSMAC VIP machine code"));
+        assert_eq!(
+            verdicts(&[0x03, 0x00]),
+            [compatible.clone(), vip_only.clone(), vip_only]
+        );
+
+        let schip = Verdict::Incompatible(String::from("uses SUPER-CHIP opcodes"));
+        assert_eq!(
+            verdicts(&[0x00, 0xFF]),
+            [schip, compatible.clone(), compatible.clone()]
+        );
+
+        let xochip = Verdict::Incompatible(String::from("uses XO-CHIP opcodes"));
+        assert_eq!(
+            verdicts(&[0xF0, 0x02]),
+            [xochip.clone(), xochip, compatible]
+        );
+
+        let shift_vy = Verdict::DependsOnQuirks(vec!["8XY6/8XYE shift VY into VX"]);
+        let shift_vx = Verdict::DependsOnQuirks(vec!["8XY6/8XYE shift VX"]);
+        assert_eq!(
+            verdicts(&[0x80, 0x16, 0x12, 0x02]),
+            [shift_vy.clone(), shift_vx, shift_vy]
+        );
+    }
+}
@@ -0,0 +1,347 @@
+use std::{
+    error::Error,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use log::{error, warn};
+
+use super::{
+    cheats::CheatList,
+    cpu::{Cpu, VIP_CALL_STACK_DEPTH},
+    error::CpuError,
+    filter::DisplayFilter,
+    frontend::{AudioSink, Colors, Control, Frame, FrontendResult, InputSource, VideoSink},
+    options::EmulatorOptions,
+    recorder::{self, Recorder},
+    timing,
+};
+
+/// Runs the CHIP-8 core independently of how it is presented.
+///
+/// Frontends own their event loop and pacing; on every tick they call
+/// [`Machine::update`] with the number of 60 Hz frames that are due and,
+/// when it asks for a redraw, [`Machine::present`] to show the new frame.
+pub struct Machine {
+    pub cpu: Cpu,
+    /// Written to memory at the start of every frame.
+    pub cheats: CheatList,
+    options: EmulatorOptions,
+    filter: DisplayFilter,
+    recorder: Option<Recorder>,
+    rom: Vec<u8>,
+    /// Frames and instructions run since the machine was created.
+    frames: u64,
+    instructions: u64,
+    /// Cycles left in the current frame with [`EmulatorOptions::vip_timing`],
+    /// negative when the last instruction ran over into the next one.
+    cycle_balance: i64,
+}
+
+impl Machine {
+    pub fn new(options: EmulatorOptions) -> Self {
+        Self {
+            cpu: Self::new_cpu(&options),
+            cheats: CheatList::default(),
+            filter: DisplayFilter::new(options.filter),
+            options,
+            recorder: None,
+            rom: vec![],
+            frames: 0,
+            instructions: 0,
+            cycle_balance: 0,
+        }
+    }
+
+    pub fn options(&self) -> &EmulatorOptions {
+        &self.options
+    }
+
+    /// Changes to the options take effect on the next [`Machine::reset`].
+    pub fn options_mut(&mut self) -> &mut EmulatorOptions {
+        &mut self.options
+    }
+
+    /// Loads a program and starts it from a fresh state.
+    /// The session recording starts with the first program, once the
+    /// platform and its resolution are known.
+    pub fn load_rom(&mut self, rom: Vec<u8>) {
+        self.rom = rom;
+        self.reset();
+
+        if let Some(missing) = self.options.platform.missing_opcodes() {
+            warn!(
+                "{} is not fully supported yet: the {} opcodes are not implemented, \
+                 running with its quirks only. They are ignored or stop the emulation.",
+                self.options.platform.name(),
+                missing
+            );
+        }
+
+        if let Some(path) = self.options.record.clone().filter(|_| self.frames == 0) {
+            if !self.is_recording() {
+                if let Err(err) = self.start_recording(&path) {
+                    error!("Cannot record to '{}': {}", path.display(), err);
+                }
+            }
+        }
+    }
+
+    pub fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    /// Restarts the loaded program.
+    pub fn reset(&mut self) {
+        self.cpu = Self::new_cpu(&self.options);
+        self.cpu.load_rom(&self.rom);
+        self.filter = DisplayFilter::new(self.options.filter);
+        self.cycle_balance = 0;
+    }
+
+    /// A CPU in its power-on state for the platform and memory layout of `options`.
+    fn new_cpu(options: &EmulatorOptions) -> Cpu {
+        let mut cpu = Cpu::new();
+        cpu.set_platform(options.platform);
+        cpu.set_program_start(options.program_start());
+        cpu.load_font(options.font, options.font_address);
+        cpu.stack_depth = options.stack_depth.unwrap_or(cpu.stack_depth);
+        cpu.stack_in_memory = options.stack_in_memory;
+        if cpu.stack_in_memory {
+            // Deeper calls would overwrite the 1802 stack and the registers.
+            cpu.stack_depth = cpu.stack_depth.min(VIP_CALL_STACK_DEPTH);
+        }
+        cpu.memory_policy = options.memory_policy;
+        cpu.quirks = options.quirks;
+        cpu
+    }
+
+    /// Applies the cheats, runs one frame worth of instructions and then ticks the timers.
+    /// Returns `true` if the screen was drawn to during the frame.
+    pub fn run_frame(&mut self) -> Result<bool, CpuError> {
+        self.cheats.apply(&mut self.cpu);
+        self.cpu.start_frame();
+
+        let mut drew = false;
+        if self.options.vip_timing {
+            self.cycle_balance += timing::INTERPRETER_CYCLES_PER_FRAME as i64;
+            while self.cycle_balance > 0 {
+                self.cycle_balance -= timing::cycles(&self.cpu) as i64;
+                drew |= self.run_instruction()?;
+                if self.cpu.waiting_for_vblank {
+                    // The rest of the frame is spent waiting.
+                    self.cycle_balance = 0;
+                }
+            }
+        } else {
+            for _ in 0..self.options.instructions_per_frame {
+                drew |= self.run_instruction()?;
+                if self.cpu.waiting_for_vblank {
+                    break;
+                }
+            }
+        }
+        self.cpu.tick_timers();
+        self.frames += 1;
+
+        Ok(drew)
+    }
+
+    /// Returns `true` if the instruction drew to the screen.
+    fn run_instruction(&mut self) -> Result<bool, CpuError> {
+        self.cpu.run_instruction()?;
+        self.instructions += 1;
+        Ok(self.cpu.draw_flag)
+    }
+
+    /// Polls the input, runs `frames` frames and updates the audio.
+    /// Returns whether the screen has to be presented again.
+    pub fn update(
+        &mut self,
+        frames: u32,
+        input: &mut dyn InputSource,
+        audio: &mut dyn AudioSink,
+    ) -> Result<(bool, Control), CpuError> {
+        let control = input.poll(&mut self.cpu.keys);
+        if control == Control::Quit {
+            return Ok((false, control));
+        }
+        input.poll_second_keypad(&mut self.cpu.keys2);
+
+        let mut redraw = false;
+        let mut samples = vec![];
+        for _ in 0..frames {
+            let drew = self.run_frame()?;
+            redraw |= drew || self.filter.is_fading();
+            self.filter.apply(&self.cpu.screen, self.cpu.width);
+            let frame_samples = self.cpu.sound_samples(recorder::SAMPLE_RATE);
+            self.record_frame(&frame_samples);
+            samples.extend(frame_samples);
+        }
+        audio.set_tone(self.cpu.sound_timer > 0);
+        if !samples.is_empty() {
+            audio.play_samples(&samples);
+        }
+
+        Ok((redraw, control))
+    }
+
+    /// Frames run since the machine was created, resets included.
+    pub fn frame_count(&self) -> u64 {
+        self.frames
+    }
+
+    /// Instructions run since the machine was created, resets included.
+    pub fn instruction_count(&self) -> u64 {
+        self.instructions
+    }
+
+    /// The screen, through the display filter, as of the last frame.
+    /// This is what [`Machine::present`] shows.
+    pub fn frame(&self) -> Frame<'_> {
+        let megachip = self
+            .cpu
+            .megachip
+            .as_ref()
+            .filter(|megachip| megachip.enabled);
+        let colors = match (&self.cpu.colors, megachip) {
+            (Some(zones), _) => Some(Colors::Zones(zones)),
+            (None, Some(megachip)) => Some(Colors::Pixels(&megachip.front)),
+            (None, None) => None,
+        };
+        Frame {
+            colors,
+            ..Frame::new(self.filter.current(), self.filter.width())
+        }
+    }
+
+    /// Sends the current screen, through the display filter, to `video`.
+    pub fn present(&mut self, video: &mut dyn VideoSink) -> FrontendResult {
+        video.present(&self.frame())
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
+
+    /// Starts recording every frame to `path`, see [`Recorder`] for the formats.
+    pub fn start_recording(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
+        self.stop_recording()?;
+        self.recorder = Some(Recorder::start(
+            path,
+            self.options.palette,
+            self.options.scaling as u32,
+            self.options.platform.max_resolution(),
+        )?);
+        Ok(())
+    }
+
+    pub fn stop_recording(&mut self) -> Result<(), Box<dyn Error>> {
+        match self.recorder.take() {
+            Some(recorder) => recorder.finish(),
+            None => Ok(()),
+        }
+    }
+
+    /// Writes the profiling report and the coverage map, if they were asked
+    /// for in the options.
+    pub fn write_reports(&self) -> Result<(), Box<dyn Error>> {
+        // Like `Cpu::load_rom`, as much of the ROM as memory holds.
+        let start = self.cpu.program_start as usize;
+        let program = start..(start + self.rom.len()).min(self.cpu.memory().len());
+        // The report converts instruction counts to time.
+        let instructions_per_frame = if self.options.vip_timing {
+            (self.instructions / self.frames.max(1)) as u32
+        } else {
+            self.options.instructions_per_frame
+        };
+
+        if let Some(path) = &self.options.profile {
+            let mut out = BufWriter::new(File::create(path)?);
+            self.cpu.profiler.write_report(
+                &mut out,
+                self.cpu.memory(),
+                program.clone(),
+                instructions_per_frame,
+            )?;
+            out.flush()?;
+            println!("Saved profile to '{}'", path.display());
+        }
+
+        if let Some(path) = &self.options.coverage {
+            let mut out = BufWriter::new(File::create(path)?);
+            self.cpu
+                .coverage
+                .write_map(&mut out, self.cpu.memory(), program)?;
+            out.flush()?;
+            println!("Saved coverage to '{}'", path.display());
+        }
+        Ok(())
+    }
+
+    /// `samples` is the MegaChip sound played during the frame, if any.
+    fn record_frame(&mut self, samples: &[i16]) {
+        if let Some(mut recorder) = self.recorder.take() {
+            let tone = self.cpu.sound_timer > 0;
+            match recorder.add_frame(&self.frame(), tone, samples) {
+                Ok(()) => self.recorder = Some(recorder),
+                Err(err) => error!("Recording stopped: {}", err),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A machine running `program` with `options`.
+    fn machine_with(options: EmulatorOptions, program: &[u8]) -> Machine {
+        let mut machine = Machine::new(options);
+        machine.load_rom(program.to_vec());
+        machine
+    }
+
+    #[test]
+    fn vip_timing_runs_a_frame_worth_of_cycles() {
+        let options = EmulatorOptions {
+            vip_timing: true,
+            ..EmulatorOptions::default()
+        };
+        // 1200 jumps to itself, 52 cycles at a time.
+        let mut machine = machine_with(options, &[0x12, 0x00]);
+        machine.run_frame().unwrap();
+        assert_eq!(machine.instruction_count(), 50);
+        // The 2 cycles the last jump ran over are taken from the next frame.
+        machine.run_frame().unwrap();
+        assert_eq!(machine.instruction_count(), 100);
+    }
+
+    #[test]
+    fn display_wait_draws_once_per_frame() {
+        let mut options = EmulatorOptions::default();
+        options.quirks.display_wait = true;
+        let mut machine = machine_with(options, &[0xD0, 0x11, 0xD0, 0x11, 0xD0, 0x11]);
+
+        // The second sprite waits for the next frame and ends this one.
+        assert!(machine.run_frame().unwrap());
+        assert_eq!(machine.cpu.profiler.draw_calls, 1);
+        assert_eq!(machine.cpu.pc, 0x202);
+        assert_eq!(machine.instruction_count(), 2);
+
+        assert!(machine.run_frame().unwrap());
+        assert_eq!(machine.cpu.profiler.draw_calls, 2);
+        assert_eq!(machine.cpu.pc, 0x204);
+    }
+
+    #[test]
+    fn sprites_are_drawn_right_away_without_display_wait() {
+        let mut machine = machine_with(
+            EmulatorOptions::default(),
+            &[0xD0, 0x11, 0xD0, 0x11, 0xD0, 0x11, 0x12, 0x06],
+        );
+        machine.run_frame().unwrap();
+        assert_eq!(machine.cpu.profiler.draw_calls, 3);
+    }
+}
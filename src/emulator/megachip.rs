@@ -0,0 +1,208 @@
+//! Mega-Chip 8, a SUPER-CHIP extension for 256x192 color demos. Programs
+//! start at CHIP-8 resolution and switch to MegaChip mode with `0011`.
+//! Sprites are then blitted from bytes of palette indices and the screen is
+//! double buffered: `00E0` shows what was drawn and starts a new frame.
+
+/// The screen in MegaChip mode.
+pub const WIDTH: usize = 256;
+pub const HEIGHT: usize = 192;
+/// Addresses are 24 bits wide.
+pub const MEMORY_SIZE: usize = 0x100_0000;
+
+/// Palette index 0 is never drawn.
+const TRANSPARENT: u8 = 0;
+/// Digitised sounds start with a 6 byte header: the sample rate (2 bytes),
+/// the length (3 bytes) and a reserved byte.
+const SOUND_HEADER_SIZE: u32 = 6;
+
+/// How sprite pixels are mixed with the screen, set by `080N`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BlendMode {
+    Normal,
+    /// The sprite is 25% opaque.
+    Quarter,
+    /// The sprite is 50% opaque.
+    Half,
+    Add,
+    Multiply,
+}
+
+/// A digitised sound played by `060N`, 8-bit unsigned samples.
+#[derive(Clone, Debug)]
+struct Sound {
+    start: u32,
+    length: u32,
+    sample_rate: u32,
+    looping: bool,
+    /// In samples of the sound, fractional when resampling.
+    position: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct MegaChip {
+    /// Set by `0011`, cleared by `0010`.
+    pub enabled: bool,
+    /// RGBA colors, loaded from memory by `02NN`.
+    pub palette: [[u8; 4]; 256],
+    /// Sprite size in pixels, set by `03NN` and `04NN`.
+    pub sprite_width: usize,
+    pub sprite_height: usize,
+    /// Opacity of the whole screen, set by `05NN`. Stored but not applied.
+    pub screen_alpha: u8,
+    pub blend_mode: BlendMode,
+    /// Drawing over a pixel of this palette index sets VF, see `09NN`.
+    pub collision_color: u8,
+    /// The frame being drawn: colors and the palette index of every pixel.
+    back: Vec<[u8; 4]>,
+    back_indices: Vec<u8>,
+    /// The frame shown since the last `00E0`.
+    pub front: Vec<[u8; 4]>,
+    sound: Option<Sound>,
+}
+
+impl MegaChip {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            palette: [[0, 0, 0, 0xFF]; 256],
+            sprite_width: 0,
+            sprite_height: 0,
+            screen_alpha: 0xFF,
+            blend_mode: BlendMode::Normal,
+            collision_color: 0,
+            back: vec![[0, 0, 0, 0xFF]; WIDTH * HEIGHT],
+            back_indices: vec![0; WIDTH * HEIGHT],
+            front: vec![[0, 0, 0, 0xFF]; WIDTH * HEIGHT],
+            sound: None,
+        }
+    }
+
+    /// ## 0x02NN
+    /// Loads `count` ARGB colors from `memory`, into palette entries 1 to `count`.
+    pub fn load_palette(&mut self, memory: &[u8], count: usize) {
+        for (index, argb) in memory.chunks_exact(4).take(count).enumerate() {
+            self.palette[index + 1] = [argb[1], argb[2], argb[3], argb[0]];
+        }
+    }
+
+    /// ## 0x080N
+    pub fn set_blend_mode(&mut self, mode: u8) {
+        self.blend_mode = match mode {
+            1 => BlendMode::Quarter,
+            2 => BlendMode::Half,
+            3 => BlendMode::Add,
+            4 => BlendMode::Multiply,
+            _ => BlendMode::Normal,
+        };
+    }
+
+    /// ## 0x00E0
+    /// Shows the frame drawn so far and clears the screen for the next one.
+    pub fn present(&mut self) {
+        std::mem::swap(&mut self.front, &mut self.back);
+        self.back.fill([0, 0, 0, 0xFF]);
+        self.back_indices.fill(TRANSPARENT);
+    }
+
+    /// ## 0xDXYN
+    /// Blits a sprite of [`MegaChip::sprite_width`] x
+    /// [`MegaChip::sprite_height`] palette indices at (`x`, `y`), clipped
+    /// at the edges. Returns `true` if it drew over the collision color.
+    pub fn draw_sprite(&mut self, sprite: &[u8], x: usize, y: usize) -> bool {
+        let mut collision = false;
+        for (row, indices) in sprite.chunks(self.sprite_width.max(1)).enumerate() {
+            for (column, index) in indices.iter().enumerate() {
+                let (x, y) = (x + column, y + row);
+                if *index == TRANSPARENT || x >= WIDTH || y >= HEIGHT {
+                    continue;
+                }
+                let pixel = y * WIDTH + x;
+                let below = self.back_indices[pixel];
+                collision |= below != TRANSPARENT && below == self.collision_color;
+                self.back_indices[pixel] = *index;
+                self.back[pixel] = self.blend(self.palette[*index as usize], self.back[pixel]);
+            }
+        }
+        collision
+    }
+
+    fn blend(&self, color: [u8; 4], below: [u8; 4]) -> [u8; 4] {
+        let mix = |opacity: u32| {
+            let channel = |c: usize| {
+                ((color[c] as u32 * opacity + below[c] as u32 * (0xFF - opacity)) / 0xFF) as u8
+            };
+            [channel(0), channel(1), channel(2), 0xFF]
+        };
+        match self.blend_mode {
+            BlendMode::Normal => mix(color[3] as u32),
+            BlendMode::Quarter => mix(0x40),
+            BlendMode::Half => mix(0x80),
+            BlendMode::Add => {
+                let channel = |c: usize| color[c].saturating_add(below[c]);
+                [channel(0), channel(1), channel(2), 0xFF]
+            }
+            BlendMode::Multiply => {
+                let channel = |c: usize| (color[c] as u32 * below[c] as u32 / 0xFF) as u8;
+                [channel(0), channel(1), channel(2), 0xFF]
+            }
+        }
+    }
+
+    /// ## 0x060N
+    /// Starts the digitised sound at `address`, looping unless `once`.
+    pub fn play_sound(&mut self, memory: &[u8], address: u32, once: bool) {
+        let byte = |offset: u32| {
+            memory
+                .get((address + offset) as usize)
+                .copied()
+                .unwrap_or(0) as u32
+        };
+        self.sound = Some(Sound {
+            start: address + SOUND_HEADER_SIZE,
+            length: (byte(2) << 16) | (byte(3) << 8) | byte(4),
+            sample_rate: (byte(0) << 8) | byte(1),
+            looping: !once,
+            position: 0.0,
+        });
+    }
+
+    /// ## 0x0700
+    pub fn stop_sound(&mut self) {
+        self.sound = None;
+    }
+
+    /// The next `count` samples of the sound playing, at `output_rate`.
+    /// Empty when no sound is playing.
+    pub fn sound_samples(&mut self, memory: &[u8], count: usize, output_rate: u32) -> Vec<i16> {
+        let Some(sound) = &mut self.sound else {
+            return vec![];
+        };
+
+        let step = sound.sample_rate as f64 / output_rate as f64;
+        let mut samples = Vec::with_capacity(count);
+        for _ in 0..count {
+            if sound.position as u32 >= sound.length {
+                if !sound.looping || sound.length == 0 {
+                    break;
+                }
+                sound.position = 0.0;
+            }
+            let address = (sound.start + sound.position as u32) as usize;
+            let sample = memory.get(address).copied().unwrap_or(0x80);
+            samples.push((sample as i16 - 0x80) << 8);
+            sound.position += step;
+        }
+
+        if samples.len() < count {
+            self.sound = None;
+            samples.resize(count, 0);
+        }
+        samples
+    }
+}
+
+impl Default for MegaChip {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,23 @@
+/// What counts as a bad memory access, and what to do about one.
+///
+/// By default memory behaves as on the VIP: addresses wrap around at the
+/// end of memory and everything is writable. The checks are opt-in, to
+/// find bugs in a ROM rather than to run it.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MemoryPolicy {
+    /// Accesses past the end of memory are violations instead of wrapping
+    /// around, e.g. `FX55` with I near `FFF`.
+    pub out_of_range: bool,
+
+    /// Writes below the program start, where the interpreter and the font
+    /// are, are violations. They are not carried out.
+    pub protect_interpreter: bool,
+
+    /// Executing bytes that instructions read or wrote as data (sprites,
+    /// `FX33`, `FX55`) is a violation. Self-modifying code trips it too.
+    pub data_execution: bool,
+
+    /// Violations stop the emulation with an error, instead of being kept
+    /// in [`super::cpu::Cpu::last_violation`] for the debugger.
+    pub stop: bool,
+}
@@ -0,0 +1,152 @@
+use super::{
+    cpu::{SCREEN_HEIGHT, SCREEN_WIDTH},
+    font::{self, ADVANCE},
+    library::{RomEntry, RomLibrary},
+};
+
+/// Height of a line of the list, the glyphs plus one pixel of spacing.
+const LINE_HEIGHT: usize = font::GLYPH_HEIGHT + 1;
+const VISIBLE_LINES: usize = SCREEN_HEIGHT / LINE_HEIGHT;
+/// Characters that fit on a line, after the one pixel margin on each side.
+const LINE_CHARS: usize = (SCREEN_WIDTH - 2) / ADVANCE;
+/// Frames between two steps of the scrolling of long titles.
+const SCROLL_FRAMES: u32 = 8;
+/// Steps a long title stays still at each end before scrolling.
+const SCROLL_PAUSE: usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MenuAction {
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Launch,
+    /// Leaves the ROMs of an archive for the list it was opened from.
+    Back,
+}
+
+/// Lists the ROMs of a [`RomLibrary`] on the CHIP-8 screen itself, so it
+/// shows up wherever the emulator output does.
+pub struct Menu {
+    library: RomLibrary,
+    selected: usize,
+    /// Index of the first visible entry.
+    top: usize,
+    /// Frames since the selection changed, drives the scrolling of long titles.
+    frames: u32,
+    /// The list an archive was opened from, see [`Menu::open`].
+    parent: Option<Box<Menu>>,
+}
+
+impl Menu {
+    pub fn new(library: RomLibrary) -> Self {
+        Self {
+            library,
+            selected: 0,
+            top: 0,
+            frames: 0,
+            parent: None,
+        }
+    }
+
+    /// Lists `library` instead, until [`MenuAction::Back`].
+    pub fn open(&mut self, library: RomLibrary) {
+        let parent = std::mem::replace(self, Menu::new(library));
+        self.parent = Some(Box::new(parent));
+    }
+
+    /// Moves the selection. Returns the entry to launch on [`MenuAction::Launch`].
+    pub fn navigate(&mut self, action: MenuAction) -> Option<&RomEntry> {
+        let last = self.library.entries.len().saturating_sub(1);
+        self.selected = match action {
+            MenuAction::Up => self.selected.saturating_sub(1),
+            MenuAction::Down => (self.selected + 1).min(last),
+            MenuAction::PageUp => self.selected.saturating_sub(VISIBLE_LINES),
+            MenuAction::PageDown => (self.selected + VISIBLE_LINES).min(last),
+            MenuAction::Launch => return self.library.entries.get(self.selected),
+            MenuAction::Back => {
+                if let Some(parent) = self.parent.take() {
+                    *self = *parent;
+                    self.frames = 0;
+                }
+                return None;
+            }
+        };
+
+        if self.selected < self.top {
+            self.top = self.selected;
+        } else if self.selected >= self.top + VISIBLE_LINES {
+            self.top = self.selected + 1 - VISIBLE_LINES;
+        }
+        self.frames = 0;
+        None
+    }
+
+    /// Advances one 60 Hz frame. Returns `true` if the menu has to be drawn again.
+    pub fn tick(&mut self) -> bool {
+        self.frames += 1;
+        self.frames.is_multiple_of(SCROLL_FRAMES) && self.selected_overflow() > 0
+    }
+
+    /// Draws the menu, one intensity per pixel like [`super::frontend::VideoSink`] expects.
+    pub fn render(&self) -> Vec<u8> {
+        let mut screen = vec![0; SCREEN_WIDTH * SCREEN_HEIGHT];
+
+        if self.library.entries.is_empty() {
+            let dir = self.library.dir.display().to_string();
+            Menu::draw_line(&mut screen, 0, "No ROMs in", false);
+            Menu::draw_line(&mut screen, 1, &dir, false);
+            return screen;
+        }
+
+        let visible = self.library.entries.iter().enumerate().skip(self.top);
+        for (line, (index, entry)) in visible.take(VISIBLE_LINES).enumerate() {
+            let selected = index == self.selected;
+            let title: String = if selected {
+                entry.title.chars().skip(self.scroll_offset()).collect()
+            } else {
+                entry.title.clone()
+            };
+            Menu::draw_line(&mut screen, line, &title, selected);
+        }
+        screen
+    }
+
+    /// Characters of the selected title that don't fit on its line.
+    fn selected_overflow(&self) -> usize {
+        self.library
+            .entries
+            .get(self.selected)
+            .map(|entry| entry.title.chars().count().saturating_sub(LINE_CHARS))
+            .unwrap_or(0)
+    }
+
+    /// How many characters of the selected title are scrolled out on the left.
+    fn scroll_offset(&self) -> usize {
+        let overflow = self.selected_overflow();
+        if overflow == 0 {
+            return 0;
+        }
+        let step = (self.frames / SCROLL_FRAMES) as usize % (overflow + 2 * SCROLL_PAUSE);
+        step.saturating_sub(SCROLL_PAUSE).min(overflow)
+    }
+
+    /// Draws `text` on the given line, inverted when `highlighted`.
+    fn draw_line(screen: &mut [u8], line: usize, text: &str, highlighted: bool) {
+        let top = line * LINE_HEIGHT;
+        let text: String = text.chars().take(LINE_CHARS).collect();
+        let text_value = if highlighted { 0 } else { 0xFF };
+
+        // The highlight spans the spacing above and below the text, which
+        // the lines around leave blank.
+        if highlighted {
+            let bottom = (top + LINE_HEIGHT + 1).min(SCREEN_HEIGHT);
+            screen[top * SCREEN_WIDTH..bottom * SCREEN_WIDTH].fill(0xFF);
+        }
+        font::draw_text(&text, 1, top + 1, |x, y| {
+            if x < SCREEN_WIDTH && y < SCREEN_HEIGHT {
+                screen[y * SCREEN_WIDTH + x] = text_value;
+            }
+        });
+    }
+}
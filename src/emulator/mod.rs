@@ -1,3 +1,39 @@
 pub mod options;
 pub mod cpu;
-pub mod emu2;
\ No newline at end of file
+pub mod emu2;
+pub mod filter;
+pub mod clock;
+pub mod keymap;
+pub mod rom;
+pub mod tui;
+pub mod error;
+pub mod frontend;
+pub mod machine;
+pub mod headless;
+pub mod palette;
+pub mod screenshot;
+pub mod recorder;
+pub mod quirks;
+pub mod octo;
+pub mod cartridge;
+pub mod platform;
+pub mod font;
+pub mod library;
+pub mod menu;
+pub mod overlay;
+pub mod disasm;
+pub mod debugger;
+pub mod cheats;
+pub mod console;
+pub mod profiler;
+pub mod coverage;
+pub mod analysis;
+pub mod lint;
+pub mod timing;
+pub mod cdp1802;
+pub mod vip;
+pub mod chip8x;
+pub mod megachip;
+pub mod fontset;
+pub mod memory_policy;
+pub mod bus;
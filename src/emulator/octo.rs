@@ -0,0 +1,867 @@
+use std::{collections::HashMap, error::Error, fmt};
+
+/// An error in an Octo program, with the line (starting at 1) where it happened.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OctoError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for OctoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for OctoError {}
+
+/// Assembles Octo source into a ROM that loads at `program_start`.
+///
+/// Supports the core of the language: labels, `:const`, `:alias`, `:unpack`,
+/// `:next`, `:org`, `:byte`, `:call`, every instruction and the structured
+/// `if`/`begin`/`else`/`end` and `loop`/`while`/`again` constructs.
+/// Metaprogramming (`:macro`, `:calc`, `:stringmode`) is not supported.
+pub fn assemble(source: &str, program_start: u16) -> Result<Vec<u8>, OctoError> {
+    let tokens = source
+        .lines()
+        .enumerate()
+        .flat_map(|(line, text)| {
+            let code = text.split('#').next().unwrap_or_default();
+            code.split_whitespace().map(move |token| (token, line + 1))
+        })
+        .collect();
+
+    Assembler::new(tokens, program_start).run()
+}
+
+#[derive(Clone, Copy)]
+enum Fixup {
+    /// Low 12 bits of the instruction at the address.
+    Nnn,
+    /// 16 bit address after `i := long`.
+    Long,
+    /// `:unpack` high byte, with the nibble to put on top (`None` for `long`).
+    UnpackHigh(Option<u8>),
+    /// `:unpack` low byte.
+    UnpackLow,
+}
+
+enum Block {
+    If {
+        jump_at: u16,
+        line: usize,
+    },
+    Else {
+        jump_at: u16,
+        line: usize,
+    },
+    Loop {
+        start: u16,
+        breaks: Vec<u16>,
+        line: usize,
+    },
+}
+
+/// A condition compiles to a few instructions setting VF (only for `<`, `>`,
+/// `<=` and `>=`) followed by a skip.
+struct Condition {
+    prelude: Vec<u16>,
+    /// Skips the next instruction when the condition doesn't hold.
+    skip_when_false: u16,
+    /// Skips the next instruction when the condition holds.
+    skip_when_true: u16,
+}
+
+struct Assembler<'a> {
+    tokens: Vec<(&'a str, usize)>,
+    position: usize,
+
+    /// Where the ROM is loaded, the address of its first byte.
+    start: u16,
+    rom: Vec<u8>,
+    here: u16,
+
+    labels: HashMap<String, u16>,
+    constants: HashMap<String, u16>,
+    aliases: HashMap<String, u8>,
+    fixups: Vec<(u16, String, Fixup, usize)>,
+    blocks: Vec<Block>,
+    next_labels: Vec<String>,
+}
+
+impl<'a> Assembler<'a> {
+    fn new(tokens: Vec<(&'a str, usize)>, start: u16) -> Self {
+        Self {
+            tokens,
+            position: 0,
+            start,
+            rom: vec![],
+            here: start,
+            labels: HashMap::new(),
+            constants: HashMap::new(),
+            aliases: HashMap::new(),
+            fixups: vec![],
+            blocks: vec![],
+            next_labels: vec![],
+        }
+    }
+
+    fn run(mut self) -> Result<Vec<u8>, OctoError> {
+        // Programs start at `main`, wherever it is.
+        self.emit_address_op(0x1000, "main", 0)?;
+
+        while self.position < self.tokens.len() {
+            self.statement()?;
+        }
+
+        if let Some(block) = self.blocks.last() {
+            let (line, message) = match block {
+                Block::If { line, .. } | Block::Else { line, .. } => {
+                    (*line, "'begin' without 'end'")
+                }
+                Block::Loop { line, .. } => (*line, "'loop' without 'again'"),
+            };
+            return Err(OctoError {
+                line,
+                message: String::from(message),
+            });
+        }
+
+        if !self.labels.contains_key("main") {
+            self.labels.insert(String::from("main"), self.start + 2);
+        }
+
+        for (address, name, fixup, line) in std::mem::take(&mut self.fixups) {
+            let value = *self.labels.get(&name).ok_or_else(|| OctoError {
+                line,
+                message: format!("undefined name '{}'", name),
+            })?;
+            let index = (address - self.start) as usize;
+            match fixup {
+                Fixup::Nnn => {
+                    if value > 0xFFF {
+                        return Err(OctoError {
+                            line,
+                            message: format!(
+                                "address of '{}' ({:#X}) does not fit in 12 bits",
+                                name, value
+                            ),
+                        });
+                    }
+                    self.rom[index] = (self.rom[index] & 0xF0) | (value >> 8) as u8;
+                    self.rom[index + 1] = value as u8;
+                }
+                Fixup::Long => {
+                    self.rom[index] = (value >> 8) as u8;
+                    self.rom[index + 1] = value as u8;
+                }
+                Fixup::UnpackHigh(Some(nibble)) => {
+                    self.rom[index] = (nibble << 4) | ((value >> 8) & 0x0F) as u8
+                }
+                Fixup::UnpackHigh(None) => self.rom[index] = (value >> 8) as u8,
+                Fixup::UnpackLow => self.rom[index] = value as u8,
+            }
+        }
+
+        Ok(self.rom)
+    }
+
+    fn error<T>(&self, message: String) -> Result<T, OctoError> {
+        Err(OctoError {
+            line: self.line(),
+            message,
+        })
+    }
+
+    /// Line of the last token read.
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.position.saturating_sub(1))
+            .map(|(_, line)| *line)
+            .unwrap_or_default()
+    }
+
+    fn next(&mut self) -> Result<&'a str, OctoError> {
+        match self.tokens.get(self.position) {
+            Some((token, _)) => {
+                self.position += 1;
+                Ok(token)
+            }
+            None => self.error(String::from("unexpected end of program")),
+        }
+    }
+
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.position).map(|(token, _)| *token)
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), OctoError> {
+        let token = self.next()?;
+        if token != expected {
+            return self.error(format!("expected '{}', found '{}'", expected, token));
+        }
+        Ok(())
+    }
+
+    fn emit_byte(&mut self, byte: u8) -> Result<(), OctoError> {
+        let Some(next) = self.here.checked_add(1) else {
+            return self.error(format!("the program runs past {:#X}", u16::MAX));
+        };
+        let index = (self.here - self.start) as usize;
+        if index >= self.rom.len() {
+            self.rom.resize(index + 1, 0);
+        }
+        self.rom[index] = byte;
+        self.here = next;
+        Ok(())
+    }
+
+    /// Address of the second byte of the next instruction, checked before
+    /// a fixup is recorded there.
+    fn operand_address(&self) -> Result<u16, OctoError> {
+        match self.here.checked_add(1) {
+            Some(address) => Ok(address),
+            None => self.error(format!("the program runs past {:#X}", u16::MAX)),
+        }
+    }
+
+    fn emit_op(&mut self, op: u16) -> Result<(), OctoError> {
+        // Past the end, the first byte fails before the label is used.
+        for name in std::mem::take(&mut self.next_labels) {
+            self.labels.insert(name, self.here.wrapping_add(1));
+        }
+        self.emit_byte((op >> 8) as u8)?;
+        self.emit_byte(op as u8)
+    }
+
+    /// Emits `op` with an address in its low 12 bits, resolved later if needed.
+    fn emit_address_op(&mut self, op: u16, token: &str, line: usize) -> Result<(), OctoError> {
+        match self.known_value(token) {
+            Some(value) if value <= 0xFFF => self.emit_op(op | value)?,
+            Some(value) => {
+                return self.error(format!("address {:#X} does not fit in 12 bits", value))
+            }
+            None => {
+                self.check_name(token)?;
+                self.fixups
+                    .push((self.here, String::from(token), Fixup::Nnn, line));
+                self.emit_op(op)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn patch_jump(&mut self, jump_at: u16, target: u16) -> Result<(), OctoError> {
+        let [high, low] = self.jump_op(target)?.to_be_bytes();
+        let index = (jump_at - self.start) as usize;
+        self.rom[index] = high;
+        self.rom[index + 1] = low;
+        Ok(())
+    }
+
+    /// `1NNN` to `target`, which the structured constructs can't jump past `FFF`.
+    fn jump_op(&self, target: u16) -> Result<u16, OctoError> {
+        if target > 0xFFF {
+            return self.error(format!("jump target {:#X} does not fit in 12 bits", target));
+        }
+        Ok(0x1000 | target)
+    }
+
+    fn number(token: &str) -> Option<i32> {
+        let (negative, digits) = match token.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, token),
+        };
+        let value = if let Some(hex) = digits.strip_prefix("0x").or(digits.strip_prefix("0X")) {
+            i32::from_str_radix(hex, 16).ok()?
+        } else if let Some(binary) = digits.strip_prefix("0b").or(digits.strip_prefix("0B")) {
+            i32::from_str_radix(binary, 2).ok()?
+        } else {
+            digits.parse::<i32>().ok()?
+        };
+        Some(if negative { -value } else { value })
+    }
+
+    /// A number, constant or already defined label.
+    fn known_value(&self, token: &str) -> Option<u16> {
+        Assembler::number(token)
+            .map(|value| value as u16)
+            .or_else(|| self.constants.get(token).copied())
+            .or_else(|| self.labels.get(token).copied())
+    }
+
+    fn byte_value(&mut self) -> Result<u8, OctoError> {
+        let token = self.next()?;
+        match Assembler::number(token) {
+            Some(value) if (-128..=255).contains(&value) => Ok(value as u8),
+            Some(value) => self.error(format!("{} does not fit in a byte", value)),
+            None => match self.known_value(token) {
+                Some(value) if value <= 0xFF => Ok(value as u8),
+                Some(value) => {
+                    self.error(format!("'{}' ({:#X}) does not fit in a byte", token, value))
+                }
+                None => self.error(format!("expected a number, found '{}'", token)),
+            },
+        }
+    }
+
+    fn nibble_value(&mut self) -> Result<u16, OctoError> {
+        let value = self.byte_value()?;
+        if value > 0xF {
+            return self.error(format!("{} does not fit in a nibble", value));
+        }
+        Ok(value as u16)
+    }
+
+    fn register(&self, token: &str) -> Option<u16> {
+        if let Some(register) = self.aliases.get(token) {
+            return Some(*register as u16);
+        }
+        let digit = token.strip_prefix('v').or(token.strip_prefix('V'))?;
+        if digit.len() != 1 {
+            return None;
+        }
+        u16::from_str_radix(digit, 16).ok()
+    }
+
+    fn expect_register(&mut self) -> Result<u16, OctoError> {
+        let token = self.next()?;
+        match self.register(token) {
+            Some(register) => Ok(register),
+            None => self.error(format!("expected a register, found '{}'", token)),
+        }
+    }
+
+    fn check_name(&self, name: &str) -> Result<(), OctoError> {
+        let valid = name
+            .chars()
+            .next()
+            .is_some_and(|first| first.is_alphabetic() || first == '_')
+            && name
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '_' || c == '-');
+        if !valid || self.register(name).is_some() {
+            return self.error(format!("invalid name '{}'", name));
+        }
+        Ok(())
+    }
+
+    fn statement(&mut self) -> Result<(), OctoError> {
+        let token = self.next()?;
+        let line = self.line();
+
+        match token {
+            ":" => {
+                let name = self.next()?;
+                self.check_name(name)?;
+                if self.labels.insert(String::from(name), self.here).is_some() {
+                    return self.error(format!("label '{}' is defined twice", name));
+                }
+            }
+            ":const" => {
+                let name = self.next()?;
+                self.check_name(name)?;
+                let value_token = self.next()?;
+                let value = match self.known_value(value_token) {
+                    Some(value) => value,
+                    None => return self.error(format!("unknown value '{}'", value_token)),
+                };
+                self.constants.insert(String::from(name), value);
+            }
+            ":alias" => {
+                let name = self.next()?;
+                self.check_name(name)?;
+                let register = self.expect_register()?;
+                self.aliases.insert(String::from(name), register as u8);
+            }
+            ":unpack" => {
+                let nibble = match self.next()? {
+                    "long" => None,
+                    nibble => match Assembler::number(nibble) {
+                        Some(value) if (0..=0xF).contains(&value) => Some(value as u8),
+                        _ => {
+                            return self
+                                .error(format!("expected a nibble or 'long', found '{}'", nibble))
+                        }
+                    },
+                };
+                let name = self.next()?;
+                match self.known_value(name) {
+                    Some(value) => {
+                        let high = match nibble {
+                            Some(nibble) => (nibble << 4) | ((value >> 8) & 0x0F) as u8,
+                            None => (value >> 8) as u8,
+                        };
+                        self.emit_op(0x6000 | high as u16)?;
+                        self.emit_op(0x6100 | (value & 0xFF))?;
+                    }
+                    None => {
+                        self.check_name(name)?;
+                        let high_at = self.operand_address()?;
+                        self.fixups.push((
+                            high_at,
+                            String::from(name),
+                            Fixup::UnpackHigh(nibble),
+                            line,
+                        ));
+                        self.emit_op(0x6000)?;
+                        let low_at = self.operand_address()?;
+                        self.fixups
+                            .push((low_at, String::from(name), Fixup::UnpackLow, line));
+                        self.emit_op(0x6100)?;
+                    }
+                }
+            }
+            ":next" => {
+                let name = self.next()?;
+                self.check_name(name)?;
+                self.next_labels.push(String::from(name));
+            }
+            ":org" => {
+                let value_token = self.next()?;
+                match self.known_value(value_token) {
+                    Some(address) if address >= self.start => self.here = address,
+                    _ => return self.error(format!("invalid address '{}'", value_token)),
+                }
+            }
+            ":byte" => {
+                let byte = self.byte_value()?;
+                self.emit_byte(byte)?;
+            }
+            ":call" => {
+                let target = self.next()?;
+                self.emit_address_op(0x2000, target, line)?;
+            }
+            ":breakpoint" => {
+                self.next()?;
+            }
+            ":monitor" => {
+                self.next()?;
+                self.next()?;
+            }
+            ":macro" | ":calc" | ":stringmode" | ":assert" | ":pointer" => {
+                return self.error(format!("'{}' is not supported", token));
+            }
+
+            "clear" => self.emit_op(0x00E0)?,
+            "return" | ";" => self.emit_op(0x00EE)?,
+            "exit" => self.emit_op(0x00FD)?,
+            "lores" => self.emit_op(0x00FE)?,
+            "hires" => self.emit_op(0x00FF)?,
+            "scroll-down" => {
+                let n = self.nibble_value()?;
+                self.emit_op(0x00C0 | n)?;
+            }
+            "scroll-up" => {
+                let n = self.nibble_value()?;
+                self.emit_op(0x00D0 | n)?;
+            }
+            "scroll-right" => self.emit_op(0x00FB)?,
+            "scroll-left" => self.emit_op(0x00FC)?,
+            "audio" => self.emit_op(0xF002)?,
+            "plane" => {
+                let n = self.nibble_value()?;
+                self.emit_op(0xF001 | (n << 8))?;
+            }
+            "jump" => {
+                let target = self.next()?;
+                self.emit_address_op(0x1000, target, line)?;
+            }
+            "jump0" => {
+                let target = self.next()?;
+                self.emit_address_op(0xB000, target, line)?;
+            }
+            "native" => {
+                let target = self.next()?;
+                self.emit_address_op(0x0000, target, line)?;
+            }
+            "sprite" => {
+                let x = self.expect_register()?;
+                let y = self.expect_register()?;
+                let n = self.nibble_value()?;
+                self.emit_op(0xD000 | (x << 8) | (y << 4) | n)?;
+            }
+            "bcd" => {
+                let x = self.expect_register()?;
+                self.emit_op(0xF033 | (x << 8))?;
+            }
+            "save" | "load" => {
+                let x = self.expect_register()?;
+                if self.peek() == Some("-") {
+                    self.next()?;
+                    let y = self.expect_register()?;
+                    let op = if token == "save" { 0x5002 } else { 0x5003 };
+                    self.emit_op(op | (x << 8) | (y << 4))?;
+                } else {
+                    let op = if token == "save" { 0xF055 } else { 0xF065 };
+                    self.emit_op(op | (x << 8))?;
+                }
+            }
+            "saveflags" => {
+                let x = self.expect_register()?;
+                self.emit_op(0xF075 | (x << 8))?;
+            }
+            "loadflags" => {
+                let x = self.expect_register()?;
+                self.emit_op(0xF085 | (x << 8))?;
+            }
+            "delay" | "buzzer" | "pitch" => {
+                self.expect(":=")?;
+                let x = self.expect_register()?;
+                let op = match token {
+                    "delay" => 0xF015,
+                    "buzzer" => 0xF018,
+                    _ => 0xF03A,
+                };
+                self.emit_op(op | (x << 8))?;
+            }
+            "i" => self.index_statement(line)?,
+
+            "if" => {
+                let condition = self.condition()?;
+                for op in &condition.prelude {
+                    self.emit_op(*op)?;
+                }
+                match self.next()? {
+                    "then" => self.emit_op(condition.skip_when_false)?,
+                    "begin" => {
+                        self.emit_op(condition.skip_when_true)?;
+                        let jump_at = self.here;
+                        self.emit_op(0x1000)?;
+                        self.blocks.push(Block::If { jump_at, line });
+                    }
+                    other => {
+                        return self.error(format!("expected 'then' or 'begin', found '{}'", other))
+                    }
+                }
+            }
+            "else" => match self.blocks.pop() {
+                Some(Block::If { jump_at, .. }) => {
+                    let else_jump = self.here;
+                    self.emit_op(0x1000)?;
+                    self.patch_jump(jump_at, self.here)?;
+                    self.blocks.push(Block::Else {
+                        jump_at: else_jump,
+                        line,
+                    });
+                }
+                _ => return self.error(String::from("'else' without 'begin'")),
+            },
+            "end" => match self.blocks.pop() {
+                Some(Block::If { jump_at, .. }) | Some(Block::Else { jump_at, .. }) => {
+                    self.patch_jump(jump_at, self.here)?
+                }
+                _ => return self.error(String::from("'end' without 'begin'")),
+            },
+            "loop" => self.blocks.push(Block::Loop {
+                start: self.here,
+                breaks: vec![],
+                line,
+            }),
+            "while" => {
+                let condition = self.condition()?;
+                for op in &condition.prelude {
+                    self.emit_op(*op)?;
+                }
+                self.emit_op(condition.skip_when_true)?;
+                let jump_at = self.here;
+                self.emit_op(0x1000)?;
+                match self
+                    .blocks
+                    .iter_mut()
+                    .rev()
+                    .find(|block| matches!(block, Block::Loop { .. }))
+                {
+                    Some(Block::Loop { breaks, .. }) => breaks.push(jump_at),
+                    _ => return self.error(String::from("'while' outside of a loop")),
+                }
+            }
+            "again" => match self.blocks.pop() {
+                Some(Block::Loop { start, breaks, .. }) => {
+                    let op = self.jump_op(start)?;
+                    self.emit_op(op)?;
+                    for jump_at in breaks {
+                        self.patch_jump(jump_at, self.here)?;
+                    }
+                }
+                _ => return self.error(String::from("'again' without 'loop'")),
+            },
+
+            _ => {
+                if let Some(x) = self.register(token) {
+                    return self.register_statement(x);
+                }
+                if let Some(value) = Assembler::number(token) {
+                    if !(-128..=255).contains(&value) {
+                        return self.error(format!("{} does not fit in a byte", value));
+                    }
+                    self.emit_byte(value as u8)?;
+                    return Ok(());
+                }
+                if let Some(value) = self.constants.get(token).copied() {
+                    if value > 0xFF {
+                        return self
+                            .error(format!("'{}' ({:#X}) does not fit in a byte", token, value));
+                    }
+                    self.emit_byte(value as u8)?;
+                    return Ok(());
+                }
+
+                // Anything else is a call to a subroutine.
+                self.emit_address_op(0x2000, token, line)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn index_statement(&mut self, line: usize) -> Result<(), OctoError> {
+        match self.next()? {
+            ":=" => match self.next()? {
+                "hex" => {
+                    let x = self.expect_register()?;
+                    self.emit_op(0xF029 | (x << 8))?;
+                }
+                "bighex" => {
+                    let x = self.expect_register()?;
+                    self.emit_op(0xF030 | (x << 8))?;
+                }
+                "long" => {
+                    let target = self.next()?;
+                    self.emit_op(0xF000)?;
+                    match self.known_value(target) {
+                        Some(value) => {
+                            self.emit_byte((value >> 8) as u8)?;
+                            self.emit_byte(value as u8)?;
+                        }
+                        None => {
+                            self.check_name(target)?;
+                            self.fixups
+                                .push((self.here, String::from(target), Fixup::Long, line));
+                            self.emit_byte(0)?;
+                            self.emit_byte(0)?;
+                        }
+                    }
+                }
+                target => self.emit_address_op(0xA000, target, line)?,
+            },
+            "+=" => {
+                let x = self.expect_register()?;
+                self.emit_op(0xF01E | (x << 8))?;
+            }
+            other => {
+                return self.error(format!(
+                    "expected ':=' or '+=' after 'i', found '{}'",
+                    other
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    fn register_statement(&mut self, x: u16) -> Result<(), OctoError> {
+        let operator = self.next()?;
+        let rhs = self.next()?;
+
+        if operator == ":=" {
+            match rhs {
+                "key" => self.emit_op(0xF00A | (x << 8))?,
+                "delay" => self.emit_op(0xF007 | (x << 8))?,
+                "random" => {
+                    let mask = self.byte_value()?;
+                    self.emit_op(0xC000 | (x << 8) | mask as u16)?;
+                }
+                _ => {
+                    if let Some(y) = self.register(rhs) {
+                        self.emit_op(0x8000 | (x << 8) | (y << 4))?;
+                    } else {
+                        self.position -= 1;
+                        let value = self.byte_value()?;
+                        self.emit_op(0x6000 | (x << 8) | value as u16)?;
+                    }
+                }
+            }
+            return Ok(());
+        }
+
+        let y = self.register(rhs);
+        let op = match (operator, y) {
+            ("+=", Some(y)) => 0x8004 | (y << 4),
+            ("-=", Some(y)) => 0x8005 | (y << 4),
+            ("=-", Some(y)) => 0x8007 | (y << 4),
+            ("|=", Some(y)) => 0x8001 | (y << 4),
+            ("&=", Some(y)) => 0x8002 | (y << 4),
+            ("^=", Some(y)) => 0x8003 | (y << 4),
+            (">>=", Some(y)) => 0x8006 | (y << 4),
+            ("<<=", Some(y)) => 0x800E | (y << 4),
+            ("+=", None) | ("-=", None) => {
+                self.position -= 1;
+                let value = self.byte_value()?;
+                let value = if operator == "-=" {
+                    value.wrapping_neg()
+                } else {
+                    value
+                };
+                0x7000 | value as u16
+            }
+            _ => return self.error(format!("cannot use '{}' with '{}'", operator, rhs)),
+        };
+        self.emit_op(op | (x << 8))?;
+        Ok(())
+    }
+
+    fn condition(&mut self) -> Result<Condition, OctoError> {
+        let x = self.expect_register()?;
+        let operator = self.next()?;
+
+        match operator {
+            "key" => {
+                return Ok(Condition {
+                    prelude: vec![],
+                    skip_when_false: 0xE0A1 | (x << 8),
+                    skip_when_true: 0xE09E | (x << 8),
+                })
+            }
+            "-key" => {
+                return Ok(Condition {
+                    prelude: vec![],
+                    skip_when_false: 0xE09E | (x << 8),
+                    skip_when_true: 0xE0A1 | (x << 8),
+                })
+            }
+            _ => {}
+        }
+
+        let rhs = self.next()?;
+        let y = self.register(rhs);
+        let value = match y {
+            Some(_) => 0,
+            None => {
+                self.position -= 1;
+                self.byte_value()? as u16
+            }
+        };
+
+        let condition = match (operator, y) {
+            ("==", Some(y)) => Condition {
+                prelude: vec![],
+                skip_when_false: 0x9000 | (x << 8) | (y << 4),
+                skip_when_true: 0x5000 | (x << 8) | (y << 4),
+            },
+            ("!=", Some(y)) => Condition {
+                prelude: vec![],
+                skip_when_false: 0x5000 | (x << 8) | (y << 4),
+                skip_when_true: 0x9000 | (x << 8) | (y << 4),
+            },
+            ("==", None) => Condition {
+                prelude: vec![],
+                skip_when_false: 0x4000 | (x << 8) | value,
+                skip_when_true: 0x3000 | (x << 8) | value,
+            },
+            ("!=", None) => Condition {
+                prelude: vec![],
+                skip_when_false: 0x3000 | (x << 8) | value,
+                skip_when_true: 0x4000 | (x << 8) | value,
+            },
+            ("<", _) | (">", _) | ("<=", _) | (">=", _) => {
+                // Loads the right hand side in VF and subtracts, leaving the
+                // "no borrow" flag in VF.
+                let load = match y {
+                    Some(y) => 0x8F00 | (y << 4),
+                    None => 0x6F00 | value,
+                };
+                let subtract = match operator {
+                    // VF = VX - rhs: no borrow when VX >= rhs.
+                    "<" | ">=" => 0x8F07 | (x << 4),
+                    // VF = rhs - VX: no borrow when VX <= rhs.
+                    _ => 0x8F05 | (x << 4),
+                };
+                let holds_when_flag = match operator {
+                    "<" | ">" => 0,
+                    _ => 1,
+                };
+                Condition {
+                    prelude: vec![load, subtract],
+                    skip_when_false: 0x4F00 | holds_when_flag,
+                    skip_when_true: 0x3F00 | holds_when_flag,
+                }
+            }
+            _ => return self.error(format!("unknown comparison '{}'", operator)),
+        };
+
+        Ok(condition)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assemble_at_200(source: &str) -> Vec<u8> {
+        assemble(source, 0x200).unwrap()
+    }
+
+    fn assemble_at_200_err(source: &str) -> OctoError {
+        assemble(source, 0x200).unwrap_err()
+    }
+
+    #[test]
+    fn labels_and_jumps() {
+        let rom = assemble_at_200(": main v0 := 5 jump main");
+        assert_eq!(rom, [0x12, 0x02, 0x60, 0x05, 0x12, 0x02]);
+    }
+
+    #[test]
+    fn forward_references_are_fixed_up() {
+        let rom = assemble_at_200(": main jump later : later v1 := 1");
+        assert_eq!(rom, [0x12, 0x02, 0x12, 0x04, 0x61, 0x01]);
+
+        let err = assemble_at_200_err(": main jump nowhere");
+        assert_eq!(err.message, "undefined name 'nowhere'");
+    }
+
+    #[test]
+    fn unpack_loads_an_address_in_v0_and_v1() {
+        let rom = assemble_at_200(": main :unpack 0xA data : data 1 2");
+        assert_eq!(rom, [0x12, 0x02, 0x60, 0xA2, 0x61, 0x06, 0x01, 0x02]);
+    }
+
+    #[test]
+    fn next_labels_the_second_byte_of_an_instruction() {
+        let rom = assemble_at_200(": main :next target v0 := 7 i := target");
+        assert_eq!(rom, [0x12, 0x02, 0x60, 0x07, 0xA2, 0x03]);
+    }
+
+    #[test]
+    fn if_begin_else_end() {
+        let rom = assemble_at_200(": main if v0 == 1 begin v1 := 1 else v1 := 2 end");
+        assert_eq!(
+            rom,
+            [0x12, 0x02, 0x30, 0x01, 0x12, 0x0A, 0x61, 0x01, 0x12, 0x0C, 0x61, 0x02]
+        );
+    }
+
+    #[test]
+    fn loop_while_again() {
+        let rom = assemble_at_200(": main loop while v0 != 3 v0 += 1 again");
+        assert_eq!(
+            rom,
+            [0x12, 0x02, 0x40, 0x03, 0x12, 0x0A, 0x70, 0x01, 0x12, 0x02]
+        );
+    }
+
+    #[test]
+    fn assembles_at_the_program_start() {
+        let rom = assemble(": main jump main", 0x600).unwrap();
+        assert_eq!(rom, [0x16, 0x02, 0x16, 0x02]);
+    }
+
+    #[test]
+    fn programs_past_ffff_are_errors() {
+        let err = assemble_at_200_err(": main :org 0xFFFF :unpack long data");
+        assert_eq!(err.message, "the program runs past 0xFFFF");
+
+        let err = assemble_at_200_err(": main :org 0x1000 loop again");
+        assert_eq!(err.message, "jump target 0x1000 does not fit in 12 bits");
+    }
+}
@@ -1,3 +1,74 @@
+use std::path::PathBuf;
+
+use super::{
+    cpu::FONTSET_START_ADDRESS, filter::FilterMode, fontset::FontSet, memory_policy::MemoryPolicy,
+    palette::Palette, platform::Platform, quirks::Quirks,
+};
+
+#[derive(Clone)]
 pub struct EmulatorOptions {
     pub scaling: u8,
-}
\ No newline at end of file
+    pub filter: FilterMode,
+    pub palette: Palette,
+
+    /// How many instructions are run between two 60 Hz timer ticks.
+    pub instructions_per_frame: u32,
+    /// Gives every instruction its COSMAC VIP cost in machine cycles instead,
+    /// see [`super::timing`].
+    pub vip_timing: bool,
+    pub platform: Platform,
+    /// Whether loading a ROM picks the platform from its extension, turned
+    /// off when the platform is given with `--platform`.
+    pub platform_from_extension: bool,
+    pub quirks: Quirks,
+    /// Loads programs there instead of the platform's program start.
+    pub load_address: Option<u16>,
+    /// The digit sprites and where they are in memory.
+    pub font: FontSet,
+    pub font_address: u16,
+    /// Overrides the call stack depth of the platform.
+    pub stack_depth: Option<usize>,
+    /// Keeps the call stack where the VIP did, see [`super::cpu::Cpu::stack_in_memory`].
+    pub stack_in_memory: bool,
+    pub memory_policy: MemoryPolicy,
+
+    /// Records the session from the start, see [`super::recorder::Recorder`].
+    pub record: Option<PathBuf>,
+    /// Where to write the profiling report on exit, see [`super::profiler::Profiler`].
+    pub profile: Option<PathBuf>,
+    /// Where to write the coverage map on exit, see [`super::coverage::Coverage`].
+    pub coverage: Option<PathBuf>,
+}
+
+/// What `chipo` runs with when no option is given.
+impl Default for EmulatorOptions {
+    fn default() -> Self {
+        Self {
+            scaling: 8,
+            filter: FilterMode::None,
+            palette: Palette::default(),
+            instructions_per_frame: 11,
+            vip_timing: false,
+            platform: Platform::Chip8,
+            platform_from_extension: true,
+            quirks: Quirks::default(),
+            load_address: None,
+            font: FontSet::default(),
+            font_address: FONTSET_START_ADDRESS,
+            stack_depth: None,
+            stack_in_memory: false,
+            memory_policy: MemoryPolicy::default(),
+            record: None,
+            profile: None,
+            coverage: None,
+        }
+    }
+}
+
+impl EmulatorOptions {
+    /// Where programs are loaded, see [`EmulatorOptions::load_address`].
+    pub fn program_start(&self) -> u16 {
+        self.load_address
+            .unwrap_or_else(|| self.platform.program_start())
+    }
+}
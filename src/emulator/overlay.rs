@@ -0,0 +1,243 @@
+use std::time::{Duration, Instant};
+
+use super::{
+    cpu::{SCREEN_HEIGHT, SCREEN_WIDTH},
+    debugger::DebugView,
+    font::{self, ADVANCE, GLYPH_HEIGHT},
+    keymap,
+};
+
+/// Overlay pixels per CHIP-8 pixel: the text is drawn finer than the screen
+/// under it.
+pub const OVERLAY_SCALE: usize = 4;
+pub const WIDTH: usize = SCREEN_WIDTH * OVERLAY_SCALE;
+pub const HEIGHT: usize = SCREEN_HEIGHT * OVERLAY_SCALE;
+
+const NOTIFICATION_DURATION: Duration = Duration::from_secs(2);
+const STATS_PERIOD: Duration = Duration::from_secs(1);
+
+const TEXT_COLOR: [u8; 4] = [0xFF, 0xFF, 0xFF, 0xFF];
+const HIGHLIGHT_COLOR: [u8; 4] = [0xFF, 0xB0, 0x00, 0xFF];
+const HIGHLIGHTED_TEXT_COLOR: [u8; 4] = [0x00, 0x00, 0x00, 0xFF];
+/// How much the boxes behind the text darken the screen, out of 255.
+const BOX_OPACITY: u16 = 0xC0;
+/// The debug panel covers the whole screen, so it hides it more.
+const PANEL_OPACITY: u16 = 0xE8;
+/// Space between the text and the edges of its box, and between the boxes
+/// and the edges of the screen.
+const PADDING: usize = 2;
+const LINE_HEIGHT: usize = GLYPH_HEIGHT + 1;
+
+/// Text drawn on top of the emulator output: notifications, the paused
+/// indicator, speed counters, the keypad layout and the debug panel.
+#[derive(Default)]
+pub struct Overlay {
+    notification: Option<(String, Instant)>,
+    pub paused: bool,
+    pub show_stats: bool,
+    pub show_key_hints: bool,
+    /// Shown over the whole screen when set, kept up to date by the frontend.
+    pub debug: Option<DebugView>,
+    stats: Stats,
+}
+
+/// Frames and instructions per second, measured over [`STATS_PERIOD`].
+#[derive(Default)]
+struct Stats {
+    since: Option<Instant>,
+    frames: u64,
+    instructions: u64,
+    fps: u64,
+    ips: u64,
+}
+
+impl Overlay {
+    /// Shows `message` for a couple of seconds.
+    pub fn notify(&mut self, message: impl Into<String>) {
+        self.notification = Some((message.into(), Instant::now()));
+    }
+
+    /// Updates the counters from the running totals of the machine, and
+    /// expires old notifications. Returns `true` if the overlay changed.
+    pub fn tick(&mut self, frames: u64, instructions: u64) -> bool {
+        let mut changed = self.stats.update(frames, instructions) && self.show_stats;
+
+        let expired = self
+            .notification
+            .as_ref()
+            .is_some_and(|(_, shown)| shown.elapsed() > NOTIFICATION_DURATION);
+        if expired {
+            self.notification = None;
+            changed = true;
+        }
+        changed
+    }
+
+    /// Draws the overlay on an RGBA `frame` of [`WIDTH`] x [`HEIGHT`] pixels.
+    pub fn draw(&self, frame: &mut [u8]) {
+        if let Some(view) = &self.debug {
+            Overlay::draw_debug_view(frame, view);
+            if self.paused {
+                let lines = [String::from("Paused")];
+                let (width, height) = Overlay::box_size(&lines);
+                Overlay::draw_box(
+                    frame,
+                    WIDTH - width - PADDING,
+                    HEIGHT - height - PADDING,
+                    &lines,
+                );
+            }
+        } else {
+            self.draw_indicators(frame);
+        }
+
+        if let Some((message, _)) = &self.notification {
+            let lines = [message.clone()];
+            let (_, height) = Overlay::box_size(&lines);
+            Overlay::draw_box(frame, PADDING, HEIGHT - height - PADDING, &lines);
+        }
+    }
+
+    fn draw_indicators(&self, frame: &mut [u8]) {
+        if self.show_stats {
+            let lines = [
+                format!("{} FPS", self.stats.fps),
+                format!("{} IPS", self.stats.ips),
+            ];
+            let (width, _) = Overlay::box_size(&lines);
+            Overlay::draw_box(frame, WIDTH - width - PADDING, PADDING, &lines);
+        }
+
+        if self.show_key_hints {
+            let lines: Vec<String> = keymap::HOST_LAYOUT
+                .iter()
+                .map(|row| {
+                    let chip8_keys: Vec<String> = row
+                        .iter()
+                        .filter_map(|key| keymap::get_chip8_key_code(key))
+                        .map(|key| format!("{:X}", key))
+                        .collect();
+                    format!("{} > {}", row.join(" "), chip8_keys.join(" "))
+                })
+                .collect();
+            Overlay::draw_centered(frame, &lines);
+        } else if self.paused {
+            Overlay::draw_centered(frame, &[String::from("Paused")]);
+        }
+    }
+
+    /// Draws the debug panel over the whole frame, highlighted characters
+    /// in dark text on a light background.
+    fn draw_debug_view(frame: &mut [u8], view: &DebugView) {
+        Overlay::darken(frame, 0, 0, WIDTH, HEIGHT, PANEL_OPACITY);
+
+        let cell =
+            |line: usize, column: usize| (PADDING + column * ADVANCE, PADDING + line * LINE_HEIGHT);
+        for highlight in &view.highlights {
+            let (x, y) = cell(highlight.line, highlight.column);
+            let right = (x + highlight.len * ADVANCE + 1).min(WIDTH);
+            let bottom = (y + LINE_HEIGHT).min(HEIGHT);
+            for row in y.saturating_sub(1)..bottom {
+                for column in x.saturating_sub(1)..right {
+                    let offset = (row * WIDTH + column) * 4;
+                    frame[offset..offset + 4].copy_from_slice(&HIGHLIGHT_COLOR);
+                }
+            }
+        }
+
+        for (line, text) in view.lines.iter().enumerate() {
+            let (x, y) = cell(line, 0);
+            font::draw_text(text, x, y, |x, y| {
+                if x >= WIDTH || y >= HEIGHT {
+                    return;
+                }
+                let column = (x - PADDING) / ADVANCE;
+                let highlighted = view.highlights.iter().any(|highlight| {
+                    highlight.line == line
+                        && (highlight.column..highlight.column + highlight.len).contains(&column)
+                });
+                let color = if highlighted {
+                    HIGHLIGHTED_TEXT_COLOR
+                } else {
+                    TEXT_COLOR
+                };
+                let offset = (y * WIDTH + x) * 4;
+                frame[offset..offset + 4].copy_from_slice(&color);
+            });
+        }
+    }
+
+    fn box_size(lines: &[String]) -> (usize, usize) {
+        let width = lines
+            .iter()
+            .map(|line| font::text_width(line))
+            .max()
+            .unwrap_or(0);
+        let height = (lines.len() * LINE_HEIGHT).saturating_sub(1);
+        (width + 2 * PADDING, height + 2 * PADDING)
+    }
+
+    fn draw_centered(frame: &mut [u8], lines: &[String]) {
+        let (width, height) = Overlay::box_size(lines);
+        Overlay::draw_box(
+            frame,
+            WIDTH.saturating_sub(width) / 2,
+            HEIGHT.saturating_sub(height) / 2,
+            lines,
+        );
+    }
+
+    /// Darkens a box with its top left corner at (`x`, `y`) and writes `lines` in it.
+    fn draw_box(frame: &mut [u8], x: usize, y: usize, lines: &[String]) {
+        let (width, height) = Overlay::box_size(lines);
+        Overlay::darken(frame, x, y, width, height, BOX_OPACITY);
+
+        for (index, line) in lines.iter().enumerate() {
+            let top = y + PADDING + index * LINE_HEIGHT;
+            font::draw_text(line, x + PADDING, top, |x, y| {
+                if x < WIDTH && y < HEIGHT {
+                    let offset = (y * WIDTH + x) * 4;
+                    frame[offset..offset + 4].copy_from_slice(&TEXT_COLOR);
+                }
+            });
+        }
+    }
+
+    /// Dims the colors of a rectangle, `opacity` out of 255.
+    fn darken(frame: &mut [u8], x: usize, y: usize, width: usize, height: usize, opacity: u16) {
+        for row in y..(y + height).min(HEIGHT) {
+            for column in x..(x + width).min(WIDTH) {
+                let offset = (row * WIDTH + column) * 4;
+                for channel in &mut frame[offset..offset + 3] {
+                    *channel = (*channel as u16 * (0xFF - opacity) / 0xFF) as u8;
+                }
+            }
+        }
+    }
+}
+
+impl Stats {
+    /// Returns `true` when new rates were computed.
+    fn update(&mut self, frames: u64, instructions: u64) -> bool {
+        let Some(since) = self.since else {
+            self.restart(frames, instructions);
+            return false;
+        };
+
+        let elapsed = since.elapsed();
+        if elapsed < STATS_PERIOD {
+            return false;
+        }
+        let per_second = |count: u64| (count as f64 / elapsed.as_secs_f64()).round() as u64;
+        self.fps = per_second(frames.saturating_sub(self.frames));
+        self.ips = per_second(instructions.saturating_sub(self.instructions));
+        self.restart(frames, instructions);
+        true
+    }
+
+    fn restart(&mut self, frames: u64, instructions: u64) {
+        self.since = Some(Instant::now());
+        self.frames = frames;
+        self.instructions = instructions;
+    }
+}
@@ -0,0 +1,52 @@
+/// Colors used to present the 1-bit CHIP-8 screen.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Palette {
+    pub foreground: [u8; 3],
+    pub background: [u8; 3],
+}
+
+impl Palette {
+    /// Parses two hex colors separated by a comma, e.g. `FFB000,1A1000`.
+    pub fn parse(value: &str) -> Option<Palette> {
+        let (foreground, background) = value.split_once(',')?;
+        Some(Palette {
+            foreground: Palette::parse_color(foreground)?,
+            background: Palette::parse_color(background)?,
+        })
+    }
+
+    fn parse_color(value: &str) -> Option<[u8; 3]> {
+        let value = value.trim().trim_start_matches('#');
+        if value.len() != 6 {
+            return None;
+        }
+        let rgb = u32::from_str_radix(value, 16).ok()?;
+        Some([(rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8])
+    }
+
+    /// Color of a pixel with the given intensity, blending from the
+    /// background (0) to the foreground (255).
+    pub fn color(&self, intensity: u8) -> [u8; 4] {
+        let blend = |background: u8, foreground: u8| {
+            let (background, foreground, intensity) =
+                (background as u32, foreground as u32, intensity as u32);
+            ((background * (0xFF - intensity) + foreground * intensity) / 0xFF) as u8
+        };
+
+        [
+            blend(self.background[0], self.foreground[0]),
+            blend(self.background[1], self.foreground[1]),
+            blend(self.background[2], self.foreground[2]),
+            0xFF,
+        ]
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self {
+            foreground: [0xFF, 0xFF, 0xFF],
+            background: [0x00, 0x00, 0x00],
+        }
+    }
+}
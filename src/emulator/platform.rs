@@ -0,0 +1,150 @@
+use std::path::Path;
+
+use super::{megachip, quirks::Quirks};
+
+/// The CHIP-8 flavour a program was written for.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Platform {
+    /// The original COSMAC VIP interpreter.
+    Chip8,
+    /// SUPER-CHIP 1.1, from the HP-48 calculators.
+    SuperChip,
+    /// Octo's XO-CHIP extension.
+    XoChip,
+    /// The VIP interpreter patched for a 64x64 screen, which programs switch
+    /// to with a `1260` jump at `0200`.
+    Hires,
+    /// CHIP-10, a 128x64 interpreter for VIPs with 4K of RAM.
+    Chip10,
+    /// CHIP-8X, for the VIP with the VP-590 color board and a second keypad.
+    Chip8X,
+    /// Mega-Chip 8, SUPER-CHIP with a 256x192 color mode and 24-bit addresses.
+    MegaChip,
+}
+
+impl Platform {
+    /// Guesses the platform from the file extension (`.ch8`, `.sc8`, `.xo8`,
+    /// `.hc8`, `.c10`, `.c8x` or `.mc8`).
+    pub fn from_path(path: &Path) -> Option<Platform> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "ch8" | "c8" => Some(Platform::Chip8),
+            "sc8" => Some(Platform::SuperChip),
+            "xo8" => Some(Platform::XoChip),
+            "hc8" | "c8h" => Some(Platform::Hires),
+            "c10" => Some(Platform::Chip10),
+            "c8x" => Some(Platform::Chip8X),
+            "mc8" => Some(Platform::MegaChip),
+            _ => None,
+        }
+    }
+
+    /// Parses a platform name as given to `--platform`.
+    pub fn parse(value: &str) -> Option<Platform> {
+        match value.to_ascii_lowercase().as_str() {
+            "chip8" | "chip-8" => Some(Platform::Chip8),
+            "schip" | "superchip" | "super-chip" => Some(Platform::SuperChip),
+            "xochip" | "xo-chip" => Some(Platform::XoChip),
+            "hires" => Some(Platform::Hires),
+            "chip10" | "chip-10" => Some(Platform::Chip10),
+            "chip8x" | "chip-8x" => Some(Platform::Chip8X),
+            "megachip" | "mega-chip" => Some(Platform::MegaChip),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Platform::Chip8 => "CHIP-8",
+            Platform::SuperChip => "SUPER-CHIP",
+            Platform::XoChip => "XO-CHIP",
+            Platform::Hires => "HIRES CHIP-8",
+            Platform::Chip10 => "CHIP-10",
+            Platform::Chip8X => "CHIP-8X",
+            Platform::MegaChip => "MEGA-CHIP",
+        }
+    }
+
+    /// The extensions chipo doesn't run yet, for platforms that are only
+    /// emulated through their quirks: `00FE`/`00FF`, scrolling, `DXY0`,
+    /// `FX30`, `FX75`/`FX85`, and XO-CHIP's planes, `F000 NNNN` and audio.
+    pub fn missing_opcodes(&self) -> Option<&'static str> {
+        match self {
+            Platform::SuperChip | Platform::MegaChip => Some("SUPER-CHIP"),
+            Platform::XoChip => Some("SUPER-CHIP and XO-CHIP"),
+            _ => None,
+        }
+    }
+
+    /// Where programs are loaded. The CHIP-8X interpreter is larger and
+    /// takes the first 3 pages.
+    pub fn program_start(&self) -> u16 {
+        match self {
+            Platform::Chip8X => 0x300,
+            _ => 0x200,
+        }
+    }
+
+    /// The screen size programs start with, in pixels.
+    pub fn resolution(&self) -> (usize, usize) {
+        match self {
+            Platform::Chip10 => (128, 64),
+            _ => (64, 32),
+        }
+    }
+
+    /// The largest screen programs can switch to.
+    pub fn max_resolution(&self) -> (usize, usize) {
+        match self {
+            Platform::Hires => (64, 64),
+            Platform::MegaChip => (megachip::WIDTH, megachip::HEIGHT),
+            _ => self.resolution(),
+        }
+    }
+
+    /// Bytes of memory, 4K except for MegaChip's 24-bit address space.
+    pub fn memory_size(&self) -> usize {
+        match self {
+            Platform::MegaChip => megachip::MEMORY_SIZE,
+            _ => 4096,
+        }
+    }
+
+    /// How many calls deep programs can go: the VIP interpreter had room
+    /// for 12 return addresses, SUPER-CHIP and its successors for 16.
+    pub fn stack_depth(&self) -> usize {
+        match self {
+            Platform::Chip8 | Platform::Hires | Platform::Chip10 | Platform::Chip8X => 12,
+            Platform::SuperChip | Platform::XoChip | Platform::MegaChip => 16,
+        }
+    }
+
+    /// The behaviour programs written for this platform expect.
+    pub fn quirks(&self) -> Quirks {
+        match self {
+            // All of them run on the VIP, which also runs their machine code.
+            Platform::Chip8 | Platform::Hires | Platform::Chip10 | Platform::Chip8X => Quirks {
+                machine_code: true,
+                ..Quirks::default()
+            },
+            Platform::SuperChip | Platform::MegaChip => Quirks {
+                vf_reset: false,
+                shift_ignores_vy: true,
+                load_store_keeps_i: true,
+                jump_uses_vx: true,
+                clip_sprites: true,
+                display_wait: false,
+                machine_code: false,
+            },
+            Platform::XoChip => Quirks {
+                vf_reset: false,
+                shift_ignores_vy: false,
+                load_store_keeps_i: false,
+                jump_uses_vx: false,
+                clip_sprites: false,
+                display_wait: false,
+                machine_code: false,
+            },
+        }
+    }
+}
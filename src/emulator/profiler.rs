@@ -0,0 +1,172 @@
+use std::{collections::HashMap, io, io::Write, ops::Range};
+
+use super::disasm::{self, Instruction};
+
+/// Hot spots listed at the top of the report.
+const HOT_SPOTS: usize = 20;
+/// Width of the heat bars in the annotated disassembly.
+const HEAT_BAR_WIDTH: u64 = 20;
+
+/// Counts what the CPU spends its instructions on.
+#[derive(Clone, Debug)]
+pub struct Profiler {
+    /// How many times the instruction at each address ran.
+    pub executions: Vec<u64>,
+    /// How many times each kind of instruction ran, by opcode pattern (`8xy4`...).
+    pub classes: HashMap<&'static str, u64>,
+    /// FX0A executions that kept waiting for a key.
+    pub key_wait_instructions: u64,
+    /// DXYN executions, and the sprite rows they drew.
+    pub draw_calls: u64,
+    pub sprite_rows: u64,
+}
+
+impl Profiler {
+    pub fn new(memory_size: usize) -> Self {
+        Self {
+            executions: vec![0; memory_size],
+            classes: HashMap::new(),
+            key_wait_instructions: 0,
+            draw_calls: 0,
+            sprite_rows: 0,
+        }
+    }
+
+    pub fn record_execution(&mut self, address: u16) {
+        if let Some(count) = self.executions.get_mut(address as usize) {
+            *count += 1;
+        }
+    }
+
+    /// Counts one more instruction of `class`. Returns `true` the first time.
+    pub fn record_class(&mut self, class: &'static str) -> bool {
+        let count = self.classes.entry(class).or_insert(0);
+        *count += 1;
+        *count == 1
+    }
+
+    pub fn total(&self) -> u64 {
+        self.executions.iter().sum()
+    }
+
+    /// Writes the counters, the hot spots and a disassembly of `program`
+    /// annotated with how often every instruction ran.
+    /// `instructions_per_frame` converts instruction counts to time.
+    pub fn write_report(
+        &self,
+        out: &mut dyn Write,
+        memory: &[u8],
+        program: Range<usize>,
+        instructions_per_frame: u32,
+    ) -> io::Result<()> {
+        let total = self.total();
+        let percent = |count: u64| 100.0 * count as f64 / total.max(1) as f64;
+        let seconds = |count: u64| count as f64 / instructions_per_frame.max(1) as f64 / 60.0;
+
+        writeln!(
+            out,
+            "Instructions executed: {} ({:.1} s at {} per frame)",
+            total,
+            seconds(total),
+            instructions_per_frame
+        )?;
+        writeln!(
+            out,
+            "Waiting for a key (FX0A): {} instructions, {:.1} s, {:.1}%",
+            self.key_wait_instructions,
+            seconds(self.key_wait_instructions),
+            percent(self.key_wait_instructions)
+        )?;
+        writeln!(
+            out,
+            "Draw calls (DXYN): {}, {} sprite rows, {} clears",
+            self.draw_calls,
+            self.sprite_rows,
+            self.classes.get("00e0").copied().unwrap_or(0)
+        )?;
+
+        writeln!(out, "\nInstructions by kind:")?;
+        let mut classes: Vec<_> = self.classes.iter().collect();
+        classes.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        for (class, count) in classes {
+            writeln!(out, "  {:6} {:>12} {:5.1}%", class, count, percent(*count))?;
+        }
+
+        writeln!(out, "\nHot spots:")?;
+        let mut hot_spots: Vec<usize> = (0..self.executions.len())
+            .filter(|address| self.executions[*address] > 0)
+            .collect();
+        hot_spots.sort_by(|a, b| self.executions[*b].cmp(&self.executions[*a]).then(a.cmp(b)));
+        for address in hot_spots.into_iter().take(HOT_SPOTS) {
+            let count = self.executions[address];
+            writeln!(
+                out,
+                "  {:04X} {:>12} {:5.1}%  {}",
+                address,
+                count,
+                percent(count),
+                Instruction::at(memory, address as u16)
+            )?;
+        }
+
+        writeln!(out, "\nAnnotated disassembly:")?;
+        let hottest = self.executions.iter().max().copied().unwrap_or(0).max(1);
+        // Follows the program two bytes at a time, but also lists instructions
+        // that ran at odd addresses.
+        let mut next = program.start;
+        for address in program.start..program.end.min(memory.len()) {
+            let count = self.executions.get(address).copied().unwrap_or(0);
+            if count == 0 && address != next {
+                continue;
+            }
+            next = address + 2;
+
+            let heat = if count == 0 {
+                String::from("-")
+            } else {
+                "#".repeat(((count * HEAT_BAR_WIDTH).div_ceil(hottest)) as usize)
+            };
+            writeln!(
+                out,
+                "  {:04X}  {:04X}  {:>12}  {:20}  {}",
+                address,
+                disasm::opcode_at(memory, address as u16),
+                count,
+                heat,
+                Instruction::at(memory, address as u16)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_executions_and_classes() {
+        let mut profiler = Profiler::new(8);
+        profiler.record_execution(2);
+        profiler.record_execution(2);
+        profiler.record_execution(100);
+        assert!(profiler.record_class("00e0"));
+        assert!(!profiler.record_class("00e0"));
+        assert_eq!(profiler.executions[2], 2);
+        assert_eq!(profiler.total(), 2);
+        assert_eq!(profiler.classes["00e0"], 2);
+    }
+
+    #[test]
+    fn report_of_a_program_past_the_end_of_memory() {
+        let memory = [0x00, 0xE0, 0x12, 0x00];
+        let mut profiler = Profiler::new(memory.len());
+        profiler.record_execution(0);
+        let mut out = vec![];
+        profiler.write_report(&mut out, &memory, 0..10, 11).unwrap();
+        let report = String::from_utf8(out).unwrap();
+        assert!(report.contains("Instructions executed: 1"));
+        assert!(report.contains("0002  1200"));
+        assert!(!report.contains("0004"));
+    }
+}
@@ -0,0 +1,47 @@
+/// Behaviours that changed between CHIP-8 interpreters, which ROMs may rely on.
+///
+/// The defaults match the original COSMAC VIP interpreter, except for
+/// [`Quirks::display_wait`] which slows most games down and
+/// [`Quirks::machine_code`] which only VIP programs expect. Both are opt-in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quirks {
+    /// `8XY1`, `8XY2` and `8XY3` reset VF to zero.
+    pub vf_reset: bool,
+
+    /// `8XY6` and `8XYE` shift VX in place, ignoring VY (CHIP-48 and SCHIP).
+    pub shift_ignores_vy: bool,
+
+    /// `FX55` and `FX65` leave I unchanged, instead of setting it to I + X + 1.
+    pub load_store_keeps_i: bool,
+
+    /// `BNNN` behaves as `BXNN`, jumping to XNN + VX instead of NNN + V0.
+    pub jump_uses_vx: bool,
+
+    /// Sprites are clipped at the edges of the screen instead of wrapping around.
+    pub clip_sprites: bool,
+
+    /// `DXYN` waits for the next frame before drawing, as the VIP waited for
+    /// the vertical blank interrupt, so at most one sprite is drawn per frame.
+    pub display_wait: bool,
+
+    /// `0NNN` runs the RCA 1802 machine code at NNN, as on the VIP, instead
+    /// of being ignored. [`Platform::quirks`] turns it on for the platforms
+    /// that ran on the VIP.
+    ///
+    /// [`Platform::quirks`]: super::platform::Platform::quirks
+    pub machine_code: bool,
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Self {
+            vf_reset: true,
+            shift_ignores_vy: false,
+            load_store_keeps_i: false,
+            jump_uses_vx: false,
+            clip_sprites: true,
+            display_wait: false,
+            machine_code: false,
+        }
+    }
+}
@@ -0,0 +1,293 @@
+use std::{
+    error::Error,
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use super::{frontend::Frame, palette::Palette, screenshot};
+
+const FRAMES_PER_SECOND: u32 = 60;
+pub const SAMPLE_RATE: u32 = 44_100;
+const SAMPLES_PER_FRAME: u32 = SAMPLE_RATE / FRAMES_PER_SECOND;
+const TONE_FREQUENCY: f32 = 440.0;
+const TONE_AMPLITUDE: i16 = i16::MAX / 4;
+
+/// Records the emulator output, one call to [`Recorder::add_frame`] per 60 Hz frame.
+///
+/// The format is picked from the file extension:
+/// - `.gif`: animated GIF, no audio. Identical frames are merged, which keeps
+///   recordings of the 1-bit display tiny. CHIP-8X and MegaChip colors are not kept.
+/// - `.y4m`: uncompressed YUV4MPEG2 video, plus a `.wav` with the buzzer next to it.
+///
+/// The video has a fixed size, frames at another resolution are stretched to it.
+pub struct Recorder {
+    path: PathBuf,
+    palette: Palette,
+    scale: u32,
+    /// Resolution of the recording, before scaling.
+    width: usize,
+    height: usize,
+    output: Output,
+}
+
+enum Output {
+    Gif {
+        encoder: gif::Encoder<BufWriter<File>>,
+        /// Frame waiting to be written, until we know how long it stays on screen.
+        pending: Option<Vec<u8>>,
+        pending_frames: u32,
+        /// Frames written so far, used to spread the 1/100 s GIF delays evenly.
+        written_frames: u32,
+    },
+    Y4m {
+        video: BufWriter<File>,
+        audio: hound::WavWriter<BufWriter<File>>,
+        tone_phase: f32,
+    },
+}
+
+impl Recorder {
+    /// Starts a recording of `resolution` pixels, each `scale` times larger.
+    pub fn start(
+        path: &Path,
+        palette: Palette,
+        scale: u32,
+        resolution: (usize, usize),
+    ) -> Result<Self, Box<dyn Error>> {
+        let scale = scale.max(1);
+        let width = resolution.0 as u32 * scale;
+        let height = resolution.1 as u32 * scale;
+
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_ascii_lowercase());
+        let output = match extension.as_deref() {
+            Some("gif") => {
+                // Frames are stored as intensities, so the color table maps
+                // every intensity to its color in the palette.
+                let color_table: Vec<u8> = (0..=0xFF)
+                    .flat_map(|intensity| palette.color(intensity)[..3].to_vec())
+                    .collect();
+                let mut encoder = gif::Encoder::new(
+                    BufWriter::new(File::create(path)?),
+                    width as u16,
+                    height as u16,
+                    &color_table,
+                )?;
+                encoder.set_repeat(gif::Repeat::Infinite)?;
+
+                Output::Gif {
+                    encoder,
+                    pending: None,
+                    pending_frames: 0,
+                    written_frames: 0,
+                }
+            }
+            Some("y4m") => {
+                let mut video = BufWriter::new(File::create(path)?);
+                writeln!(
+                    video,
+                    "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C444",
+                    width, height, FRAMES_PER_SECOND
+                )?;
+                let audio = hound::WavWriter::create(
+                    path.with_extension("wav"),
+                    hound::WavSpec {
+                        channels: 1,
+                        sample_rate: SAMPLE_RATE,
+                        bits_per_sample: 16,
+                        sample_format: hound::SampleFormat::Int,
+                    },
+                )?;
+
+                Output::Y4m {
+                    video,
+                    audio,
+                    tone_phase: 0.0,
+                }
+            }
+            _ => {
+                return Err(
+                    format!("cannot record to '{}', use .gif or .y4m", path.display()).into(),
+                )
+            }
+        };
+
+        println!("Recording to '{}'", path.display());
+        Ok(Self {
+            path: path.to_path_buf(),
+            palette,
+            scale,
+            width: resolution.0,
+            height: resolution.1,
+            output,
+        })
+    }
+
+    /// Adds one frame. `frame` is the presented screen
+    /// (see [`super::frontend::VideoSink`]), `tone` whether the buzzer is on
+    /// and `samples` the MegaChip sound played instead, if any.
+    pub fn add_frame(
+        &mut self,
+        frame: &Frame,
+        tone: bool,
+        samples: &[i16],
+    ) -> Result<(), Box<dyn Error>> {
+        let resized;
+        let frame = if (frame.width, frame.height) == (self.width, self.height) {
+            frame
+        } else {
+            resized = Recorder::resize(frame, self.width, self.height);
+            &Frame::new(&resized, self.width)
+        };
+        let intensities = frame.intensities;
+
+        match &mut self.output {
+            Output::Gif {
+                encoder,
+                pending,
+                pending_frames,
+                written_frames,
+            } => {
+                if pending.as_deref() == Some(intensities) {
+                    *pending_frames += 1;
+                    return Ok(());
+                }
+
+                if let Some(frame) = pending.take() {
+                    Recorder::write_gif_frame(
+                        encoder,
+                        &Frame::new(&frame, self.width),
+                        self.scale,
+                        *pending_frames,
+                        written_frames,
+                    )?;
+                }
+                *pending = Some(intensities.to_vec());
+                *pending_frames = 1;
+            }
+            Output::Y4m {
+                video,
+                audio,
+                tone_phase,
+            } => {
+                let rgba = screenshot::to_rgba(frame, &self.palette, self.scale);
+                let (mut y, mut u, mut v) = (vec![], vec![], vec![]);
+                for pixel in rgba.chunks_exact(4) {
+                    let (r, g, b) = (pixel[0] as f32, pixel[1] as f32, pixel[2] as f32);
+                    // BT.601, studio swing.
+                    y.push((16.0 + 0.257 * r + 0.504 * g + 0.098 * b).round() as u8);
+                    u.push((128.0 - 0.148 * r - 0.291 * g + 0.439 * b).round() as u8);
+                    v.push((128.0 + 0.439 * r - 0.368 * g - 0.071 * b).round() as u8);
+                }
+                video.write_all(b"FRAME\n")?;
+                video.write_all(&y)?;
+                video.write_all(&u)?;
+                video.write_all(&v)?;
+
+                if !samples.is_empty() {
+                    for sample in samples {
+                        audio.write_sample(*sample)?;
+                    }
+                    return Ok(());
+                }
+
+                // The buzzer is a square wave.
+                for _ in 0..SAMPLES_PER_FRAME {
+                    let sample = match (tone, *tone_phase < 0.5) {
+                        (false, _) => 0,
+                        (true, true) => TONE_AMPLITUDE,
+                        (true, false) => -TONE_AMPLITUDE,
+                    };
+                    audio.write_sample(sample)?;
+                    *tone_phase = (*tone_phase + TONE_FREQUENCY / SAMPLE_RATE as f32).fract();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Flushes everything to disk.
+    pub fn finish(self) -> Result<(), Box<dyn Error>> {
+        match self.output {
+            Output::Gif {
+                mut encoder,
+                pending,
+                pending_frames,
+                mut written_frames,
+            } => {
+                if let Some(frame) = pending {
+                    Recorder::write_gif_frame(
+                        &mut encoder,
+                        &Frame::new(&frame, self.width),
+                        self.scale,
+                        pending_frames,
+                        &mut written_frames,
+                    )?;
+                }
+                encoder.into_inner()?.flush()?;
+            }
+            Output::Y4m {
+                mut video, audio, ..
+            } => {
+                video.flush()?;
+                audio.finalize()?;
+            }
+        }
+
+        println!("Saved recording to '{}'", self.path.display());
+        Ok(())
+    }
+
+    /// Stretches `frame` to `width` x `height`, keeping the nearest pixel.
+    fn resize(frame: &Frame, width: usize, height: usize) -> Vec<u8> {
+        (0..height)
+            .flat_map(|y| {
+                (0..width).map(move |x| {
+                    let source_y = y * frame.height / height;
+                    let source_x = x * frame.width / width;
+                    frame.intensities[source_y * frame.width + source_x]
+                })
+            })
+            .collect()
+    }
+
+    fn write_gif_frame(
+        encoder: &mut gif::Encoder<BufWriter<File>>,
+        frame: &Frame,
+        scale: u32,
+        frames: u32,
+        written_frames: &mut u32,
+    ) -> Result<(), Box<dyn Error>> {
+        let scale = scale as usize;
+        let mut buffer = Vec::with_capacity(frame.intensities.len() * scale * scale);
+        for row in frame.intensities.chunks_exact(frame.width) {
+            let line: Vec<u8> = row
+                .iter()
+                .flat_map(|intensity| [*intensity].repeat(scale))
+                .collect();
+            for _ in 0..scale {
+                buffer.extend_from_slice(&line);
+            }
+        }
+
+        // GIF delays are in 1/100 s, which 60 Hz frames don't divide evenly:
+        // round the time at which the frame ends instead of its duration.
+        let centiseconds = |frames: u32| (frames * 100 + FRAMES_PER_SECOND / 2) / FRAMES_PER_SECOND;
+        let start = centiseconds(*written_frames);
+        *written_frames += frames;
+        let delay = centiseconds(*written_frames) - start;
+
+        let mut gif_frame = gif::Frame::from_indexed_pixels(
+            (frame.width * scale) as u16,
+            (frame.height * scale) as u16,
+            buffer,
+            None,
+        );
+        gif_frame.delay = delay.min(u16::MAX as u32) as u16;
+        encoder.write_frame(&gif_frame)?;
+        Ok(())
+    }
+}
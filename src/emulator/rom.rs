@@ -0,0 +1,255 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufRead, Read, Write},
+    path::Path,
+};
+
+use super::{
+    cartridge::{self, Cartridge},
+    options::EmulatorOptions,
+    platform::Platform,
+    quirks::Quirks,
+};
+
+pub fn load_rom_file(path: &str) -> io::Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    let mut buffer = vec![];
+    let bytes_read = file.read_to_end(&mut buffer)?;
+
+    println!("Loaded '{}' ({} bytes read)", path, bytes_read);
+
+    Ok(buffer)
+}
+
+/// Loads a program from `path`. `entry` picks the ROM of a ZIP archive or
+/// directory; without it the collection is searched for one, asking on
+/// stdin when there are several. Octo cartridges are decoded. The platform guessed from the
+/// ROM extension and the settings a cartridge embeds are applied to `options`.
+pub fn load_rom(
+    path: &str,
+    entry: Option<&str>,
+    options: &mut EmulatorOptions,
+) -> io::Result<Vec<u8>> {
+    let (name, data) = if is_zip(path) {
+        load_rom_from_zip(path, entry)?
+    } else if Path::new(path).is_dir() {
+        load_rom_from_dir(path, entry)?
+    } else {
+        (path.to_string(), load_rom_file(path)?)
+    };
+
+    let detected =
+        Platform::from_path(Path::new(&name)).filter(|_| options.platform_from_extension);
+    if let Some(platform) = detected {
+        println!("Detected platform: {:?}", platform);
+        options.platform = platform;
+        // Display wait is opt-in whatever the platform.
+        options.quirks = Quirks {
+            display_wait: options.quirks.display_wait,
+            ..platform.quirks()
+        };
+    }
+
+    if !cartridge::is_cartridge(&data) {
+        return Ok(data);
+    }
+
+    let cartridge = Cartridge::decode(&data, options.program_start()).map_err(|err| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid Octo cartridge: {}", err),
+        )
+    })?;
+    cartridge.apply_options(options);
+    Ok(cartridge.program)
+}
+
+pub fn is_zip(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("zip"))
+}
+
+/// The names of the ROMs inside a ZIP archive, sorted.
+pub fn zip_roms(path: &str) -> io::Result<Vec<String>> {
+    let archive = zip::ZipArchive::new(File::open(path)?)?;
+    let mut roms: Vec<String> = archive
+        .file_names()
+        .filter(|name| Platform::from_path(Path::new(name)).is_some())
+        .map(String::from)
+        .collect();
+    roms.sort();
+    Ok(roms)
+}
+
+/// Whether `path` holds ROMs to pick from: a ZIP archive or a directory.
+pub fn is_collection(path: &str) -> bool {
+    is_zip(path) || Path::new(path).is_dir()
+}
+
+/// The names of the ROMs of a ZIP archive or directory, sorted.
+pub fn collection_roms(path: &str) -> io::Result<Vec<String>> {
+    if is_zip(path) {
+        zip_roms(path)
+    } else {
+        dir_roms(path)
+    }
+}
+
+/// The names of the ROM files of a directory, sorted.
+pub fn dir_roms(path: &str) -> io::Result<Vec<String>> {
+    let mut roms = vec![];
+    for dir_entry in fs::read_dir(path)? {
+        let dir_entry = dir_entry?;
+        let name = dir_entry.file_name().to_string_lossy().into_owned();
+        if dir_entry.path().is_file() && Platform::from_path(Path::new(&name)).is_some() {
+            roms.push(name);
+        }
+    }
+    roms.sort();
+    Ok(roms)
+}
+
+/// `entry`, or the only ROM `list` finds in `path`, asking which one to
+/// load when there are several.
+fn pick_rom(
+    path: &str,
+    entry: Option<&str>,
+    list: fn(&str) -> io::Result<Vec<String>>,
+) -> io::Result<String> {
+    if let Some(entry) = entry {
+        return Ok(entry.to_string());
+    }
+    let mut roms = list(path)?;
+    match roms.len() {
+        0 => Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no CHIP-8 ROM in '{}'", path),
+        )),
+        1 => Ok(roms.remove(0)),
+        _ => choose_rom(&roms),
+    }
+}
+
+/// Reads the ROM `entry` of a directory, or the only one, see [`pick_rom`].
+/// Returns the file name along with its content.
+fn load_rom_from_dir(path: &str, entry: Option<&str>) -> io::Result<(String, Vec<u8>)> {
+    let name = pick_rom(path, entry, dir_roms)?;
+    let data = load_rom_file(&Path::new(path).join(&name).to_string_lossy())?;
+    Ok((name, data))
+}
+
+/// Reads the ROM `entry` of a ZIP archive, or the only one, see [`pick_rom`].
+/// Returns the entry name along with its content.
+fn load_rom_from_zip(path: &str, entry: Option<&str>) -> io::Result<(String, Vec<u8>)> {
+    let name = pick_rom(path, entry, zip_roms)?;
+
+    let mut archive = zip::ZipArchive::new(File::open(path)?)?;
+    let mut entry = archive.by_name(&name)?;
+    let mut buffer = vec![];
+    let bytes_read = entry.read_to_end(&mut buffer)?;
+
+    println!(
+        "Loaded '{}' from '{}' ({} bytes read)",
+        name, path, bytes_read
+    );
+
+    Ok((name, buffer))
+}
+
+/// Lists `roms` and reads the number of the one to load from stdin.
+pub fn choose_rom(roms: &[String]) -> io::Result<String> {
+    for (index, name) in roms.iter().enumerate() {
+        println!("{:>3}: {}", index + 1, name);
+    }
+
+    let stdin = io::stdin();
+    loop {
+        print!("ROM to load [1-{}]: ", roms.len());
+        io::stdout().flush()?;
+
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "no ROM was chosen",
+            ));
+        }
+        match line.trim().parse::<usize>() {
+            Ok(choice) if (1..=roms.len()).contains(&choice) => return Ok(roms[choice - 1].clone()),
+            _ => println!("Please enter a number between 1 and {}", roms.len()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, path::PathBuf, process};
+
+    use zip::write::SimpleFileOptions;
+
+    use super::*;
+
+    /// A path in the temporary directory, unique to this test run.
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("chipo-{}-{}", process::id(), name))
+    }
+
+    fn write_zip(name: &str, files: &[(&str, &[u8])]) -> String {
+        let path = temp_path(name);
+        let mut zip = zip::ZipWriter::new(File::create(&path).unwrap());
+        for (name, data) in files {
+            zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(data).unwrap();
+        }
+        zip.finish().unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn lists_the_roms_of_an_archive() {
+        let path = write_zip(
+            "list.zip",
+            &[("b.sc8", &[1]), ("readme.txt", &[2]), ("a.ch8", &[3])],
+        );
+        assert!(is_collection(&path));
+        assert_eq!(collection_roms(&path).unwrap(), ["a.ch8", "b.sc8"]);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn loads_the_only_rom_or_the_entry_of_an_archive() {
+        let mut options = EmulatorOptions::default();
+        let single = write_zip("single.zip", &[("game.ch8", &[0x12, 0x00])]);
+        assert_eq!(load_rom(&single, None, &mut options).unwrap(), [0x12, 0x00]);
+
+        let several = write_zip("several.zip", &[("a.ch8", &[1]), ("b.sc8", &[2])]);
+        let rom = load_rom(&several, Some("b.sc8"), &mut options).unwrap();
+        assert_eq!(rom, [2]);
+        assert_eq!(options.platform, Platform::SuperChip);
+        assert!(options.quirks.load_store_keeps_i);
+
+        fs::remove_file(single).unwrap();
+        fs::remove_file(several).unwrap();
+    }
+
+    #[test]
+    fn loads_roms_from_a_directory() {
+        let dir = temp_path("dir");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("game.xo8"), [0x12, 0x00]).unwrap();
+        fs::write(dir.join("notes.txt"), "").unwrap();
+        let path = dir.to_string_lossy().into_owned();
+
+        assert!(is_collection(&path));
+        assert_eq!(collection_roms(&path).unwrap(), ["game.xo8"]);
+        let mut options = EmulatorOptions::default();
+        assert_eq!(load_rom(&path, None, &mut options).unwrap(), [0x12, 0x00]);
+        assert_eq!(options.platform, Platform::XoChip);
+
+        fs::remove_file(dir.join("game.xo8")).unwrap();
+        let err = load_rom(&path, None, &mut options).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
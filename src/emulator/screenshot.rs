@@ -0,0 +1,129 @@
+use std::{
+    error::Error,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use super::{frontend::Frame, palette::Palette};
+
+/// Converts a presented frame to RGBA, each pixel repeated `scale` times
+/// horizontally and vertically.
+pub fn to_rgba(frame: &Frame, palette: &Palette, scale: u32) -> Vec<u8> {
+    let scale = scale.max(1) as usize;
+    let mut rgba = Vec::with_capacity(frame.intensities.len() * scale * scale * 4);
+
+    for y in 0..frame.height {
+        let line: Vec<u8> = (0..frame.width)
+            .flat_map(|x| frame.color(x, y, palette).repeat(scale))
+            .collect();
+        for _ in 0..scale {
+            rgba.extend_from_slice(&line);
+        }
+    }
+
+    rgba
+}
+
+/// Encodes a presented frame (see [`super::frontend::VideoSink`]) as a PNG.
+pub fn write_png<W: Write>(
+    writer: W,
+    frame: &Frame,
+    palette: &Palette,
+    scale: u32,
+) -> Result<(), Box<dyn Error>> {
+    let scale = scale.max(1);
+    let mut encoder = png::Encoder::new(
+        writer,
+        frame.width as u32 * scale,
+        frame.height as u32 * scale,
+    );
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut png_writer = encoder.write_header()?;
+    png_writer.write_image_data(&to_rgba(frame, palette, scale))?;
+    Ok(())
+}
+
+pub fn save_png(
+    path: &Path,
+    frame: &Frame,
+    palette: &Palette,
+    scale: u32,
+) -> Result<(), Box<dyn Error>> {
+    let file = BufWriter::new(File::create(path)?);
+    write_png(file, frame, palette, scale)?;
+    println!("Saved screenshot to '{}'", path.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::{frontend::NullFrontend, machine::Machine, options::EmulatorOptions};
+
+    const ON: [u8; 4] = [0xFF, 0xB0, 0x00, 0xFF];
+    const OFF: [u8; 4] = [0x1A, 0x10, 0x00, 0xFF];
+
+    fn amber() -> Palette {
+        Palette::parse("FFB000,1A1000").unwrap()
+    }
+
+    /// Decodes a PNG to its size and RGBA pixels.
+    fn decode(data: &[u8]) -> (u32, u32, Vec<u8>) {
+        let mut reader = png::Decoder::new(data).read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+        pixels.truncate(info.buffer_size());
+        (info.width, info.height, pixels)
+    }
+
+    /// `rows` of `#` (on) and `.` (off) as RGBA.
+    fn golden(rows: &[&str]) -> Vec<u8> {
+        rows.iter()
+            .flat_map(|row| row.chars())
+            .flat_map(|pixel| if pixel == '#' { ON } else { OFF })
+            .collect()
+    }
+
+    #[test]
+    fn pixels_are_scaled_both_ways() {
+        let frame = Frame::new(&[0xFF, 0x00], 2);
+        assert_eq!(to_rgba(&frame, &amber(), 2), golden(&["##..", "##.."]));
+    }
+
+    #[test]
+    fn screenshots_match_the_screen() {
+        // 6001 6101 F029 D015 1208: the digit 0 at (1, 1).
+        let program = [0x60, 0x01, 0x61, 0x01, 0xF2, 0x29, 0xD0, 0x15, 0x12, 0x08];
+        let mut machine = Machine::new(EmulatorOptions::default());
+        machine.load_rom(program.to_vec());
+        // Like `--screenshot-at-frame`, through the display filter.
+        machine
+            .update(1, &mut NullFrontend, &mut NullFrontend)
+            .unwrap();
+
+        let mut data = vec![];
+        write_png(&mut data, &machine.frame(), &amber(), 1).unwrap();
+        let (width, height, pixels) = decode(&data);
+        assert_eq!((width, height), (64, 32));
+
+        let top_left: Vec<u8> = pixels
+            .chunks(64 * 4)
+            .take(7)
+            .flat_map(|row| row[..6 * 4].to_vec())
+            .collect();
+        #[rustfmt::skip]
+        let expected = golden(&[
+            "......",
+            ".####.",
+            ".#..#.",
+            ".#..#.",
+            ".#..#.",
+            ".####.",
+            "......",
+        ]);
+        assert_eq!(top_left, expected);
+    }
+}
@@ -0,0 +1,130 @@
+//! Instruction costs of the original COSMAC VIP interpreter.
+//!
+//! The VIP's 1802 runs at 1.76 MHz, 8 clocks per machine cycle. Every frame
+//! the 1861 video chip steals cycles to fetch the display through DMA and an
+//! interrupt routine ticks the timers, and the interpreter gets the rest.
+//! Costs are in machine cycles and include fetching and decoding the
+//! instruction. They follow the interpreter's routines, so they are close but
+//! not exact: memory layout and interrupts landing mid-instruction are not
+//! taken into account.
+
+use super::{cpu::Cpu, disasm::Instruction};
+
+/// 1.76 MHz / 8 clocks per cycle / 60 Hz.
+pub const CYCLES_PER_FRAME: u32 = 3668;
+/// 128 scanlines of 8 bytes each, fetched by the 1861.
+pub const DISPLAY_DMA_CYCLES: u32 = 1024;
+/// The interrupt routine that starts the display and ticks the timers.
+pub const INTERRUPT_CYCLES: u32 = 46;
+/// What is left for the interpreter every frame.
+pub const INTERPRETER_CYCLES_PER_FRAME: u32 =
+    CYCLES_PER_FRAME - DISPLAY_DMA_CYCLES - INTERRUPT_CYCLES;
+
+/// Fetching the opcode and jumping to its routine.
+const FETCH_CYCLES: u32 = 40;
+/// Skipping is one more increment of the program counter.
+const SKIP_CYCLES: u32 = 4;
+
+/// Cycles taken by the instruction at PC, given the state it will run in.
+pub fn cycles(cpu: &Cpu) -> u32 {
+    let instruction = Instruction::at(cpu.memory(), cpu.pc);
+    let skip = |skips: bool| if skips { SKIP_CYCLES } else { 0 };
+    let key = |x: usize| cpu.keys.get(cpu.v[x] as usize).copied().unwrap_or(false);
+
+    FETCH_CYCLES
+        + match instruction {
+            // A loop over the 256 bytes of the display buffer.
+            Instruction::Clear => 24 + 256 * 6,
+            Instruction::Return => 10,
+            Instruction::Jump(_) => 12,
+            Instruction::Call(_) => 26,
+            Instruction::SkipEqual { x, nn } => 10 + skip(cpu.v[x] == nn),
+            Instruction::SkipNotEqual { x, nn } => 10 + skip(cpu.v[x] != nn),
+            Instruction::SkipEqualRegister { x, y } => 14 + skip(cpu.v[x] == cpu.v[y]),
+            Instruction::SkipNotEqualRegister { x, y } => 14 + skip(cpu.v[x] != cpu.v[y]),
+            Instruction::Load { .. } => 6,
+            Instruction::Add { .. } => 10,
+            // 8XYN builds and runs a small routine on the stack.
+            Instruction::Move { .. }
+            | Instruction::Or { .. }
+            | Instruction::And { .. }
+            | Instruction::Xor { .. }
+            | Instruction::AddRegister { .. }
+            | Instruction::Sub { .. }
+            | Instruction::ShiftRight { .. }
+            | Instruction::SubReverse { .. }
+            | Instruction::ShiftLeft { .. } => 44,
+            Instruction::LoadI(_) => 12,
+            Instruction::JumpOffset(_) => 22,
+            Instruction::Random { .. } => 36,
+            Instruction::Draw { x, n, .. } => draw_cycles(cpu.v[x], n, cpu.width),
+            Instruction::SkipKey { x } => 14 + skip(key(x)),
+            Instruction::SkipNotKey { x } => 14 + skip(!key(x)),
+            Instruction::LoadDelay { .. } | Instruction::SetDelay { .. } => 10,
+            Instruction::SetSound { .. } => 10,
+            // Every poll of the keypad while waiting.
+            Instruction::WaitKey { .. } => 18,
+            Instruction::AddI { .. } => 16,
+            Instruction::LoadFont { .. } => 20,
+            // Digits are found by repeated subtraction.
+            Instruction::Bcd { x } => {
+                let value = cpu.v[x] as u32;
+                80 + 16 * (value / 100 + value / 10 % 10 + value % 10)
+            }
+            Instruction::Store { x } | Instruction::Restore { x } => 14 + 14 * (x as u32 + 1),
+            Instruction::Sys(_) => 10,
+            Instruction::Unknown(_) => 0,
+        }
+}
+
+/// Each sprite row is shifted into place, and written to two bytes of the
+/// display buffer instead of one when X isn't a multiple of 8.
+fn draw_cycles(x: u8, rows: u8, width: usize) -> u32 {
+    let aligned = (x as usize % width).is_multiple_of(8);
+    let row_cycles = if aligned { 46 } else { 76 };
+    26 + rows as u32 * row_cycles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The cost of the first instruction of `program`, with `v0` in V0.
+    fn cost(program: &[u8], v0: u8) -> u32 {
+        let mut cpu = Cpu::new();
+        cpu.load_rom(program);
+        cpu.v[0] = v0;
+        cycles(&cpu)
+    }
+
+    #[test]
+    fn costs_include_the_fetch() {
+        assert_eq!(cost(&[0x12, 0x00], 0), FETCH_CYCLES + 12);
+        assert_eq!(cost(&[0x60, 0x01], 0), FETCH_CYCLES + 6);
+        assert_eq!(cost(&[0x00, 0xE0], 0), FETCH_CYCLES + 24 + 256 * 6);
+    }
+
+    #[test]
+    fn skips_cost_more_when_taken() {
+        assert_eq!(cost(&[0x30, 0x05], 5), FETCH_CYCLES + 10 + SKIP_CYCLES);
+        assert_eq!(cost(&[0x30, 0x05], 4), FETCH_CYCLES + 10);
+        assert_eq!(cost(&[0x40, 0x05], 4), FETCH_CYCLES + 10 + SKIP_CYCLES);
+    }
+
+    #[test]
+    fn bcd_costs_grow_with_the_digits() {
+        assert_eq!(cost(&[0xF0, 0x33], 0), FETCH_CYCLES + 80);
+        assert_eq!(cost(&[0xF0, 0x33], 255), FETCH_CYCLES + 80 + 16 * 12);
+    }
+
+    #[test]
+    fn unaligned_sprites_cost_more_per_row() {
+        assert_eq!(cost(&[0xD0, 0x05], 8), FETCH_CYCLES + 26 + 5 * 46);
+        assert_eq!(cost(&[0xD0, 0x05], 3), FETCH_CYCLES + 26 + 5 * 76);
+    }
+
+    #[test]
+    fn the_interpreter_gets_what_the_display_leaves() {
+        assert_eq!(INTERPRETER_CYCLES_PER_FRAME, 2598);
+    }
+}
@@ -0,0 +1,307 @@
+use std::{
+    error::Error,
+    io::{self, Stdout, Write},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crossterm::{
+    cursor,
+    event::{
+        self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+        PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+    },
+    execute, queue,
+    style::Print,
+    terminal::{self, ClearType},
+};
+use log::debug;
+
+use super::{
+    clock::FrameClock,
+    cpu::Cpu,
+    frontend::{AudioSink, Colors, Control, Frame, FrontendResult, InputSource, VideoSink},
+    keymap,
+    machine::Machine,
+    options::EmulatorOptions,
+    rom,
+};
+
+/// Most terminals only report key presses (and auto-repeat), never releases.
+/// On those, a key is considered held until it has not been seen for this long.
+const KEY_HOLD_DURATION: Duration = Duration::from_millis(150);
+
+/// Renders the emulator in a terminal, two pixels per character cell.
+/// Useful over SSH, where there's no display to open a window on.
+pub struct Tui {
+    options: EmulatorOptions,
+    rom: Option<Vec<u8>>,
+}
+
+impl Tui {
+    pub fn new(options: EmulatorOptions) -> Self {
+        Self { options, rom: None }
+    }
+
+    pub fn load_rom(&mut self, path: &str) -> Result<(), io::Error> {
+        let program_data = rom::load_rom(path, None, &mut self.options)?;
+        self.rom = Some(program_data);
+        Ok(())
+    }
+
+    pub fn run(self) -> Result<(), Box<dyn Error>> {
+        let mut machine = Machine::new(self.options.clone());
+        if let Some(rom) = self.rom {
+            machine.load_rom(rom);
+        } else {
+            panic!("No rom was loaded!");
+        }
+
+        let mut stdout = io::stdout();
+        terminal::enable_raw_mode()?;
+        execute!(stdout, terminal::EnterAlternateScreen, cursor::Hide)?;
+
+        // Lets us see key releases on terminals that support it (kitty, foot, wezterm...).
+        let reports_releases = terminal::supports_keyboard_enhancement().unwrap_or(false);
+        if reports_releases {
+            execute!(
+                stdout,
+                PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+            )?;
+        }
+
+        let result = Tui::run_loop(&mut machine, reports_releases);
+        let recording = machine.stop_recording();
+        let reports = machine.write_reports();
+
+        if reports_releases {
+            execute!(stdout, PopKeyboardEnhancementFlags)?;
+        }
+        execute!(stdout, cursor::Show, terminal::LeaveAlternateScreen)?;
+        terminal::disable_raw_mode()?;
+
+        result.and(recording).and(reports)
+    }
+
+    fn run_loop(machine: &mut Machine, reports_releases: bool) -> Result<(), Box<dyn Error>> {
+        let mut video = TerminalVideo {
+            out: io::stdout(),
+            size: None,
+        };
+        let mut input = TerminalInput {
+            reports_releases,
+            key_last_seen: [None; 16],
+            keys2: [false; 16],
+            key2_last_seen: [None; 16],
+        };
+        let mut audio = TerminalAudio { playing: false };
+        let mut frame_clock = FrameClock::new();
+
+        // Draws the border around the screen.
+        machine.present(&mut video)?;
+
+        loop {
+            thread::sleep(
+                frame_clock
+                    .next_frame()
+                    .saturating_duration_since(Instant::now()),
+            );
+
+            let (redraw, control) =
+                machine.update(frame_clock.frames_due(), &mut input, &mut audio)?;
+            if control == Control::Quit {
+                return Ok(());
+            }
+
+            if redraw {
+                machine.present(&mut video)?;
+            }
+            video.draw_panel(&machine.cpu)?;
+            video.out.flush()?;
+        }
+    }
+}
+
+/// Draws the screen with half-block characters, each cell holding two pixels
+/// stacked vertically.
+struct TerminalVideo {
+    out: Stdout,
+    /// Size of the screen the border was drawn around, if it was.
+    size: Option<(usize, usize)>,
+}
+
+impl TerminalVideo {
+    /// Clears the terminal and draws a border around a `width` x `height` screen.
+    fn draw_border(&mut self, width: usize, height: usize) -> io::Result<()> {
+        let horizontal = "─".repeat(width);
+        queue!(
+            self.out,
+            terminal::Clear(ClearType::All),
+            cursor::MoveTo(0, 0),
+            Print(format!("┌{}┐", horizontal)),
+            cursor::MoveTo(0, (height / 2) as u16 + 1),
+            Print(format!("└{}┘", horizontal)),
+        )?;
+        for row in 1..=(height / 2) as u16 {
+            queue!(
+                self.out,
+                cursor::MoveTo(0, row),
+                Print("│"),
+                cursor::MoveTo(width as u16 + 1, row),
+                Print("│"),
+            )?;
+        }
+        self.size = Some((width, height));
+        Ok(())
+    }
+
+    /// Column where the register panel starts, one cell after the screen border.
+    fn panel_column(&self) -> u16 {
+        self.size.map_or(0, |(width, _)| width as u16 + 3)
+    }
+
+    fn draw_panel(&mut self, cpu: &Cpu) -> io::Result<()> {
+        let keys: String = (0..16)
+            .map(|key| {
+                if cpu.keys[key] {
+                    format!("{:X}", key)
+                } else {
+                    String::from(".")
+                }
+            })
+            .collect();
+        let timers = format!(
+            "DT: {:#04X} | ST: {:#04X}",
+            cpu.delay_timer, cpu.sound_timer
+        );
+
+        let state = format!("{:?}", cpu);
+        let lines = state.lines().map(String::from).chain([
+            timers,
+            format!("Keys: {}", keys),
+            String::from("Esc to quit"),
+        ]);
+
+        let column = self.panel_column();
+        for (row, line) in lines.enumerate() {
+            queue!(
+                self.out,
+                cursor::MoveTo(column, row as u16 + 1),
+                terminal::Clear(ClearType::UntilNewLine),
+                Print(line),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl VideoSink for TerminalVideo {
+    fn present(&mut self, frame: &Frame) -> FrontendResult {
+        if self.size != Some((frame.width, frame.height)) {
+            self.draw_border(frame.width, frame.height)?;
+        }
+        // MegaChip colors are shown as lit when they are bright enough.
+        let lit = |x: usize, y: usize| match frame.colors {
+            Some(Colors::Pixels(pixels)) => {
+                let [r, g, b, _] = pixels[y * frame.width + x];
+                r as u16 + g as u16 + b as u16 >= 0x180
+            }
+            _ => frame.intensities[y * frame.width + x] >= 0x80,
+        };
+
+        for row in 0..frame.height / 2 {
+            let line: String = (0..frame.width)
+                .map(|x| match (lit(x, row * 2), lit(x, row * 2 + 1)) {
+                    (true, true) => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (false, false) => ' ',
+                })
+                .collect();
+            queue!(self.out, cursor::MoveTo(1, row as u16 + 1), Print(line))?;
+        }
+        Ok(())
+    }
+}
+
+/// Rings the terminal bell when the buzzer starts.
+struct TerminalAudio {
+    playing: bool,
+}
+
+impl AudioSink for TerminalAudio {
+    fn set_tone(&mut self, playing: bool) {
+        if playing && !self.playing {
+            let _ = queue!(io::stdout(), Print('\x07'));
+        }
+        self.playing = playing;
+    }
+}
+
+struct TerminalInput {
+    reports_releases: bool,
+    key_last_seen: [Option<Instant>; 16],
+    /// The second keypad of CHIP-8X, handed over by `poll_second_keypad`.
+    keys2: [bool; 16],
+    key2_last_seen: [Option<Instant>; 16],
+}
+
+impl TerminalInput {
+    fn is_quit(key_event: &KeyEvent) -> bool {
+        key_event.code == KeyCode::Esc
+            || (key_event.code == KeyCode::Char('c')
+                && key_event.modifiers.contains(KeyModifiers::CONTROL))
+    }
+
+    fn handle(&mut self, key_event: &KeyEvent, keys: &mut [bool; 16]) {
+        if let KeyCode::Char(c) = key_event.code {
+            let keystr = c.to_ascii_lowercase().to_string();
+            let pressed = key_event.kind != KeyEventKind::Release;
+            if let Some(chip8_key) = keymap::get_second_keypad_key_code(&keystr) {
+                self.keys2[chip8_key as usize] = pressed;
+                self.key2_last_seen[chip8_key as usize] = pressed.then(Instant::now);
+            }
+            if let Some(chip8_key) = keymap::get_chip8_key_code(&keystr) {
+                debug!(
+                    "keyboard event: {} -> {}",
+                    &keystr,
+                    if pressed { "pressed" } else { "released" }
+                );
+                keys[chip8_key as usize] = pressed;
+                self.key_last_seen[chip8_key as usize] = pressed.then(Instant::now);
+            }
+        }
+    }
+
+    fn release_stale_keys(key_last_seen: &mut [Option<Instant>; 16], keys: &mut [bool; 16]) {
+        for (key, last_seen) in key_last_seen.iter_mut().enumerate() {
+            if last_seen.is_some_and(|seen| seen.elapsed() > KEY_HOLD_DURATION) {
+                *last_seen = None;
+                keys[key] = false;
+            }
+        }
+    }
+}
+
+impl InputSource for TerminalInput {
+    fn poll(&mut self, keys: &mut [bool; 16]) -> Control {
+        while event::poll(Duration::ZERO).unwrap_or(false) {
+            if let Ok(Event::Key(key_event)) = event::read() {
+                if TerminalInput::is_quit(&key_event) {
+                    return Control::Quit;
+                }
+                self.handle(&key_event, keys);
+            }
+        }
+
+        if !self.reports_releases {
+            TerminalInput::release_stale_keys(&mut self.key_last_seen, keys);
+            TerminalInput::release_stale_keys(&mut self.key2_last_seen, &mut self.keys2);
+        }
+        Control::Continue
+    }
+
+    fn poll_second_keypad(&mut self, keys: &mut [bool; 16]) {
+        keys.copy_from_slice(&self.keys2);
+    }
+}
@@ -0,0 +1,265 @@
+//! The COSMAC VIP itself: an 1802, 4K of RAM, the monitor ROM, the CDP1861
+//! video chip, the hex keypad and the tone generator. It runs the original
+//! CHIP-8 interpreter, which makes it the reference `Cpu` is compared to.
+//!
+//! Neither ROM image ships with chipo: the 512 byte monitor is mapped at
+//! `8000` and the 512 byte CHIP-8 interpreter is loaded at `0000`, as the
+//! VIP owner would have done from tape.
+
+use std::io;
+
+use super::{
+    cdp1802::{Bus, Cdp1802},
+    cpu::{Cpu, PROGRAM_START, SCREEN_HEIGHT, SCREEN_WIDTH, VIP_REGISTERS_ADDRESS},
+};
+
+pub const RAM_SIZE: usize = 4096;
+const MONITOR_SIZE: usize = 512;
+const MONITOR_ADDRESS: u16 = 0x8000;
+
+/// The 1861 draws 262 lines of 14 machine cycles per frame (3668 cycles).
+const LINES_PER_FRAME: u32 = 262;
+const CYCLES_PER_LINE: i64 = 14;
+/// Bytes fetched by DMA on every displayed line, one cycle each.
+const DMA_BYTES_PER_LINE: usize = 8;
+/// The interrupt is raised two lines before the first displayed one.
+const INTERRUPT_LINE: u32 = 62;
+const FIRST_DISPLAY_LINE: u32 = 64;
+pub const DISPLAY_LINES: usize = 128;
+/// EF1 is asserted during the 4 lines before the display starts and ends.
+const DISPLAY_FLAG_LINES: u32 = 4;
+
+/// Memory and devices as the 1802 sees them.
+struct VipBus {
+    ram: Vec<u8>,
+    monitor: Vec<u8>,
+    /// After a reset the monitor shows through the whole address space,
+    /// until an address with A15 set is used.
+    monitor_overlay: bool,
+    /// The 1861 is turned on by `INP 1` and off by `OUT 1`.
+    display_on: bool,
+    /// EF1, set by the 1861 around the displayed lines.
+    display_flag: bool,
+    /// The key `OUT 2` selected, whose state is read on EF3.
+    selected_key: u8,
+    keys: [bool; 16],
+}
+
+impl Bus for VipBus {
+    fn read(&mut self, address: u16) -> u8 {
+        if address >= MONITOR_ADDRESS {
+            self.monitor_overlay = false;
+        }
+        if address >= MONITOR_ADDRESS || self.monitor_overlay {
+            self.monitor[address as usize % MONITOR_SIZE]
+        } else {
+            self.ram[address as usize % RAM_SIZE]
+        }
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        if address < MONITOR_ADDRESS {
+            self.ram[address as usize % RAM_SIZE] = value;
+        }
+    }
+
+    fn output(&mut self, port: u8, value: u8) {
+        match port {
+            1 => self.display_on = false,
+            2 => self.selected_key = value & 0xF,
+            _ => {}
+        }
+    }
+
+    fn input(&mut self, port: u8) -> u8 {
+        if port == 1 {
+            self.display_on = true;
+        }
+        0
+    }
+
+    fn flag(&mut self, n: u8) -> bool {
+        match n {
+            1 => self.display_flag,
+            3 => self.keys[self.selected_key as usize],
+            _ => false,
+        }
+    }
+}
+
+pub struct Vip {
+    pub cpu: Cdp1802,
+    bus: VipBus,
+    /// The last frame as drawn by the 1861, 64 x 128 lines, 0 or 255 per pixel.
+    pub scanlines: Vec<u8>,
+    /// Cycles left in the current line, negative when an instruction ran over.
+    cycle_balance: i64,
+    pub frames: u64,
+}
+
+impl Vip {
+    /// Powers on a VIP with the given monitor ROM and CHIP-8 interpreter.
+    pub fn new(monitor: Vec<u8>, interpreter: Vec<u8>) -> io::Result<Vip> {
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+        if monitor.len() != MONITOR_SIZE {
+            return Err(invalid(format!(
+                "the monitor ROM should be {} bytes, not {}",
+                MONITOR_SIZE,
+                monitor.len()
+            )));
+        }
+        if interpreter.len() > PROGRAM_START as usize {
+            return Err(invalid(format!(
+                "the interpreter should fit in {} bytes, not {}",
+                PROGRAM_START,
+                interpreter.len()
+            )));
+        }
+
+        let mut ram = vec![0; RAM_SIZE];
+        ram[..interpreter.len()].copy_from_slice(&interpreter);
+        Ok(Vip {
+            cpu: Cdp1802::new(),
+            bus: VipBus {
+                ram,
+                monitor,
+                monitor_overlay: true,
+                display_on: false,
+                display_flag: false,
+                selected_key: 0,
+                keys: [false; 16],
+            },
+            scanlines: vec![0; SCREEN_WIDTH * DISPLAY_LINES],
+            cycle_balance: 0,
+            frames: 0,
+        })
+    }
+
+    /// Loads a CHIP-8 program after the interpreter.
+    pub fn load_program(&mut self, program: &[u8]) {
+        let start = PROGRAM_START as usize;
+        let end = (start + program.len()).min(RAM_SIZE);
+        self.bus.ram[start..end].copy_from_slice(&program[..end - start]);
+    }
+
+    pub fn set_keys(&mut self, keys: [bool; 16]) {
+        self.bus.keys = keys;
+    }
+
+    /// The tone generator is driven by Q.
+    pub fn tone(&self) -> bool {
+        self.cpu.q
+    }
+
+    /// Runs one frame, line by line: the 1861 takes its DMA cycles on the
+    /// displayed lines and the 1802 gets the rest.
+    pub fn run_frame(&mut self) {
+        let display = FIRST_DISPLAY_LINE..FIRST_DISPLAY_LINE + DISPLAY_LINES as u32;
+        for line in 0..LINES_PER_FRAME {
+            let on = self.bus.display_on;
+            self.bus.display_flag = on
+                && ((display.start - DISPLAY_FLAG_LINES..display.start).contains(&line)
+                    || (display.end - DISPLAY_FLAG_LINES..display.end).contains(&line));
+            if on && line == INTERRUPT_LINE && self.cpu.ie {
+                self.cpu.interrupt();
+            }
+
+            let mut cycles = CYCLES_PER_LINE;
+            if on && display.contains(&line) {
+                self.dma_line((line - display.start) as usize);
+                cycles -= DMA_BYTES_PER_LINE as i64;
+            }
+
+            self.cycle_balance += cycles;
+            while self.cycle_balance > 0 {
+                self.cycle_balance -= self.cpu.step(&mut self.bus) as i64;
+            }
+        }
+
+        if !self.bus.display_on {
+            self.scanlines.fill(0);
+        }
+        self.frames += 1;
+    }
+
+    /// Fetches the bytes of one line at R0, as the 1861 does.
+    fn dma_line(&mut self, line: usize) {
+        for byte_index in 0..DMA_BYTES_PER_LINE {
+            let byte = self.bus.read(self.cpu.r[0]);
+            self.cpu.r[0] = self.cpu.r[0].wrapping_add(1);
+            for bit in 0..8 {
+                let lit = byte & (0x80 >> bit) != 0;
+                self.scanlines[line * SCREEN_WIDTH + byte_index * 8 + bit] =
+                    if lit { 0xFF } else { 0 };
+            }
+        }
+    }
+
+    /// The display at CHIP-8 resolution: the interpreter shows every row
+    /// on 4 lines.
+    pub fn screen(&self) -> Vec<u8> {
+        let repeat = DISPLAY_LINES / SCREEN_HEIGHT;
+        self.scanlines
+            .chunks(SCREEN_WIDTH)
+            .step_by(repeat)
+            .flatten()
+            .copied()
+            .collect()
+    }
+
+    /// V0-VF, where the interpreter keeps them.
+    pub fn chip8_registers(&self) -> [u8; 16] {
+        let start = VIP_REGISTERS_ADDRESS as usize;
+        let mut v = [0; 16];
+        v.copy_from_slice(&self.bus.ram[start..start + 16]);
+        v
+    }
+
+    /// The interpreter keeps I in RA.
+    pub fn chip8_i(&self) -> u16 {
+        self.cpu.r[0xA]
+    }
+
+    /// The interpreter keeps the CHIP-8 program counter in R5.
+    pub fn chip8_pc(&self) -> u16 {
+        self.cpu.r[5]
+    }
+
+    /// Lists where `cpu` disagrees with the VIP: registers, I, PC and the
+    /// number of pixels that differ.
+    pub fn compare(&self, cpu: &Cpu) -> Vec<String> {
+        let mut differences = vec![];
+        for (index, (vip, chipo)) in self.chip8_registers().iter().zip(cpu.v).enumerate() {
+            if *vip != chipo {
+                differences.push(format!(
+                    "V{:X}: VIP {:02X}, chipo {:02X}",
+                    index, vip, chipo
+                ));
+            }
+        }
+        if self.chip8_i() as u32 != cpu.i {
+            differences.push(format!(
+                "I: VIP {:03X}, chipo {:03X}",
+                self.chip8_i(),
+                cpu.i
+            ));
+        }
+        if self.chip8_pc() != cpu.pc {
+            differences.push(format!(
+                "PC: VIP {:03X}, chipo {:03X}",
+                self.chip8_pc(),
+                cpu.pc
+            ));
+        }
+        let pixels = self
+            .screen()
+            .iter()
+            .zip(cpu.screen.iter())
+            .filter(|(vip, chipo)| (**vip != 0) != (**chipo != 0))
+            .count();
+        if pixels > 0 {
+            differences.push(format!("{} pixels differ", pixels));
+        }
+        differences
+    }
+}
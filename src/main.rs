@@ -1,23 +1,343 @@
-use std::env;
+use std::{env, path::PathBuf};
 
-use chipo::emulator::{emu2::Emu2, options::EmulatorOptions};
+use chipo::emulator::{
+    analysis::Analysis,
+    cpu::VIP_CALL_STACK_DEPTH,
+    emu2::Emu2,
+    filter::FilterMode,
+    fontset::{FontSet, FONT_SIZE},
+    headless::Headless,
+    library,
+    library::RomLibrary,
+    lint::LintReport,
+    options::EmulatorOptions,
+    palette::Palette,
+    platform::Platform,
+    quirks::Quirks,
+    rom,
+    tui::Tui,
+    vip::Vip,
+};
 
+/// The highest address of the CHIP-8 memory.
+const MEMORY_END: u16 = 0xFFF;
+/// A sanity limit for `--stack-depth`, far above what any interpreter had.
+/// With `--stack-in-memory` the limit is [`VIP_CALL_STACK_DEPTH`] instead.
+const MAX_STACK_DEPTH: usize = 64;
 
 fn main() {
-    env::set_var("RUST_LOG", "debug");
+    let mut options = EmulatorOptions::default();
+    let mut rom_path = None;
+    let mut rom_dir = env::var_os("CHIPO_ROM_DIR").map(PathBuf::from);
+    let mut use_terminal = false;
+    let mut cheat_console = false;
+    let mut headless_frames = None;
+    let mut screenshot_path = None;
+    let mut screenshot_scale = 1;
+    let mut cfg_path = None;
+    let mut vip_images = None;
+
+    let mut args = env::args().skip(1).peekable();
+    // The other options still apply, for the platform and load address.
+    let lint_only = args.next_if(|arg| arg == "lint").is_some();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--filter" => {
+                let value = args.next().expect("--filter expects a value");
+                options.filter = FilterMode::parse(&value).unwrap_or_else(|| {
+                    panic!(
+                        "Unknown filter '{}', expected none, phosphor[:frames] or blend[:weight].",
+                        value
+                    )
+                });
+            }
+            "--ipf" => {
+                let value = args.next().expect("--ipf expects a value");
+                options.instructions_per_frame = value
+                    .parse()
+                    .unwrap_or_else(|_| panic!("Invalid instructions per frame '{}'.", value));
+            }
+            "--platform" => {
+                let value = args.next().expect("--platform expects a name");
+                let platform = Platform::parse(&value).unwrap_or_else(|| {
+                    panic!("Unknown platform '{}', expected chip8, schip, xochip, hires, chip10, chip8x or megachip.", value)
+                });
+                options.platform = platform;
+                options.platform_from_extension = false;
+                options.quirks = Quirks {
+                    display_wait: options.quirks.display_wait,
+                    ..platform.quirks()
+                };
+            }
+            "--load-address" => {
+                let value = args.next().expect("--load-address expects a hex address");
+                options.load_address = Some(
+                    parse_address(&value)
+                        .filter(|address| *address <= MEMORY_END)
+                        .unwrap_or_else(|| panic!("Invalid load address '{}'.", value)),
+                );
+            }
+            "--font" => {
+                let value = args.next().expect("--font expects a name");
+                options.font = FontSet::parse(&value).unwrap_or_else(|| {
+                    panic!(
+                        "Unknown font '{}', expected octo, vip, dream6800, eti660 or schip.",
+                        value
+                    )
+                });
+            }
+            "--font-address" => {
+                let value = args.next().expect("--font-address expects a hex address");
+                options.font_address = parse_address(&value)
+                    .filter(|address| *address as usize + FONT_SIZE <= MEMORY_END as usize + 1)
+                    .unwrap_or_else(|| panic!("Invalid font address '{}'.", value));
+            }
+            "--stack-depth" => {
+                let value = args.next().expect("--stack-depth expects a value");
+                options.stack_depth = Some(
+                    value
+                        .parse()
+                        .ok()
+                        .filter(|depth| (1..=MAX_STACK_DEPTH).contains(depth))
+                        .unwrap_or_else(|| {
+                            panic!(
+                                "Invalid stack depth '{}', expected 1 to {}.",
+                                value, MAX_STACK_DEPTH
+                            )
+                        }),
+                );
+            }
+            "--stack-in-memory" => options.stack_in_memory = true,
+            "--memory-bounds" => options.memory_policy.out_of_range = true,
+            "--protect-interpreter" => options.memory_policy.protect_interpreter = true,
+            "--flag-data-execution" => options.memory_policy.data_execution = true,
+            "--stop-on-violation" => options.memory_policy.stop = true,
+            "--vip-timing" => options.vip_timing = true,
+            "--display-wait" => options.quirks.display_wait = true,
+            "--vip" => {
+                let monitor = args.next().expect("--vip expects a monitor ROM path");
+                let interpreter = args.next().expect("--vip expects an interpreter path");
+                vip_images = Some((monitor, interpreter));
+            }
+            "--palette" => {
+                let value = args.next().expect("--palette expects a value");
+                options.palette = Palette::parse(&value).unwrap_or_else(|| {
+                    panic!(
+                        "Invalid palette '{}', expected FOREGROUND,BACKGROUND hex colors.",
+                        value
+                    )
+                });
+            }
+            "--screenshot-at-frame" => {
+                let frame = args
+                    .next()
+                    .expect("--screenshot-at-frame expects a frame number");
+                let path = args
+                    .next()
+                    .expect("--screenshot-at-frame expects an output path");
+                headless_frames = Some(
+                    frame
+                        .parse::<u32>()
+                        .unwrap_or_else(|_| panic!("Invalid frame number '{}'.", frame)),
+                );
+                screenshot_path = Some(PathBuf::from(path));
+            }
+            "--screenshot-scale" => {
+                let value = args.next().expect("--screenshot-scale expects a value");
+                screenshot_scale = value
+                    .parse()
+                    .unwrap_or_else(|_| panic!("Invalid screenshot scale '{}'.", value));
+            }
+            "--record" => {
+                let path = args.next().expect("--record expects an output path");
+                options.record = Some(PathBuf::from(path));
+            }
+            "--rom-dir" => {
+                let path = args.next().expect("--rom-dir expects a directory");
+                rom_dir = Some(PathBuf::from(path));
+            }
+            "--profile" => {
+                let path = args.next().expect("--profile expects an output path");
+                options.profile = Some(PathBuf::from(path));
+            }
+            "--coverage" => {
+                let path = args.next().expect("--coverage expects an output path");
+                options.coverage = Some(PathBuf::from(path));
+            }
+            "--cfg" => {
+                let path = args.next().expect("--cfg expects an output path");
+                cfg_path = Some(PathBuf::from(path));
+            }
+            "--cheats" => cheat_console = true,
+            "--tui" => use_terminal = true,
+            "--headless" => {
+                let value = args.next().expect("--headless expects a number of frames");
+                headless_frames = Some(
+                    value
+                        .parse::<u32>()
+                        .unwrap_or_else(|_| panic!("Invalid number of frames '{}'.", value)),
+                );
+            }
+            _ if rom_path.is_none() => rom_path = Some(arg),
+            _ => panic!("Unexpected argument '{}'.", arg),
+        }
+    }
+
+    if lint_only {
+        let rom_path = rom_path.expect("Expected a rom path.");
+        lint(&rom_path, &mut options);
+        return;
+    }
+
+    if vip_images.is_some() && headless_frames.is_none() {
+        panic!("--vip requires --headless N.");
+    }
+
+    let stack_depth = options.stack_depth.unwrap_or(0);
+    if options.stack_in_memory && stack_depth > VIP_CALL_STACK_DEPTH {
+        panic!(
+            "--stack-in-memory holds at most {} return addresses, not {}.",
+            VIP_CALL_STACK_DEPTH, stack_depth
+        );
+    }
+
+    // Logs would be drawn over the terminal frontend.
+    env::set_var("RUST_LOG", if use_terminal { "off" } else { "debug" });
     env_logger::init();
 
-    let args: Vec<String> = env::args().collect();
+    if let Some(path) = cfg_path {
+        let rom_path = rom_path.expect("Expected a rom path.");
+        let rom = match rom::load_rom(&rom_path, None, &mut options) {
+            Ok(rom) => rom,
+            Err(err) => {
+                println!("Cannot open rom! {}", err);
+                return;
+            }
+        };
+        let analysis = Analysis::analyze(&rom, options.program_start());
+        match std::fs::write(&path, analysis.to_dot()) {
+            Ok(()) => println!(
+                "Saved control flow graph ({} blocks, {} subroutines) to '{}'",
+                analysis.blocks.len(),
+                analysis.subroutines.len(),
+                path.display()
+            ),
+            Err(err) => println!("Cannot save control flow graph! {}", err),
+        }
+        return;
+    }
 
-    if args.len() != 2 {
-        panic!("Expected 1 argument, got {} instead.", args.len() - 1);
+    if let Some(frames) = headless_frames {
+        let rom_path = rom_path.expect("Expected a rom path.");
+        if vip_images.is_some() {
+            // Runs as close to the VIP as possible for the comparison.
+            options.vip_timing = true;
+            options.quirks.display_wait = true;
+        }
+        let mut headless = Headless::new(options);
+        headless.load_rom(&rom_path).unwrap_or_else(|err| {
+            println!("Cannot open rom! {}", err);
+        });
+        if let Err(err) = headless.run(frames) {
+            println!("Emulation error! {}", err);
+        }
+        if let Err(err) = headless.machine.stop_recording() {
+            println!("Cannot save recording! {}", err);
+        }
+        if let Err(err) = headless.machine.write_reports() {
+            println!("Cannot save reports! {}", err);
+        }
+        println!("{:?}", headless.machine.cpu);
+        if let Some((monitor, interpreter)) = vip_images {
+            compare_with_vip(&monitor, &interpreter, &headless, frames);
+        }
+        if let Some(path) = screenshot_path {
+            if let Err(err) = headless.screenshot(&path, screenshot_scale) {
+                println!("Cannot save screenshot! {}", err);
+            }
+        }
+        return;
     }
 
-    let mut emu2 = Emu2::new(EmulatorOptions {
-        scaling: 8,
-    });
-    emu2.load_rom(&args[1]).unwrap_or_else(|err| {
-        println!("Cannot open rom! {}", err);
-    });
+    if use_terminal {
+        let rom_path = rom_path.expect("Expected a rom path.");
+        let mut tui = Tui::new(options);
+        tui.load_rom(&rom_path).unwrap_or_else(|err| {
+            println!("Cannot open rom! {}", err);
+        });
+        if let Err(err) = tui.run() {
+            println!("Terminal error! {}", err);
+        }
+        return;
+    }
+
+    let mut emu2 = Emu2::new(options);
+    let rom_dir = rom_dir.unwrap_or_else(|| PathBuf::from(library::DEFAULT_ROM_DIR));
+    match RomLibrary::scan(&rom_dir) {
+        Ok(library) => emu2.set_library(library),
+        Err(err) if rom_path.is_none() => {
+            println!("Cannot open rom directory '{}'! {}", rom_dir.display(), err);
+            return;
+        }
+        Err(_) => {}
+    }
+    if let Some(rom_path) = rom_path {
+        emu2.load_rom(&rom_path).unwrap_or_else(|err| {
+            println!("Cannot open rom! {}", err);
+        });
+    }
+    if cheat_console {
+        emu2.enable_cheat_console();
+    }
     emu2.run();
 }
+
+/// `chipo lint [options] <rom>`: reports code that may not run the same everywhere.
+fn lint(rom_path: &str, options: &mut EmulatorOptions) {
+    let rom = match rom::load_rom(rom_path, None, options) {
+        Ok(rom) => rom,
+        Err(err) => {
+            println!("Cannot open rom! {}", err);
+            return;
+        }
+    };
+    let report = LintReport::lint(&rom, options.program_start());
+    if let Err(err) = report.write(&mut std::io::stdout()) {
+        println!("Cannot write lint report! {}", err);
+    }
+}
+
+/// Runs the ROM on an emulated COSMAC VIP for as many frames as the CPU ran,
+/// and shows where they disagree.
+fn compare_with_vip(monitor: &str, interpreter: &str, headless: &Headless, frames: u32) {
+    let vip = std::fs::read(monitor)
+        .and_then(|monitor| Ok((monitor, std::fs::read(interpreter)?)))
+        .and_then(|(monitor, interpreter)| Vip::new(monitor, interpreter));
+    let mut vip = match vip {
+        Ok(vip) => vip,
+        Err(err) => {
+            println!("Cannot start the VIP! {}", err);
+            return;
+        }
+    };
+    vip.load_program(headless.machine.rom());
+    for _ in 0..frames {
+        vip.run_frame();
+    }
+
+    println!("VIP PC: {:#X} | I: {:#X}", vip.chip8_pc(), vip.chip8_i());
+    let differences = vip.compare(&headless.machine.cpu);
+    if differences.is_empty() {
+        println!("Same state as the VIP.");
+    }
+    for difference in differences {
+        println!("Differs from the VIP: {}", difference);
+    }
+}
+
+/// Parses a hex address, with or without `0x`.
+fn parse_address(value: &str) -> Option<u16> {
+    let digits = value.strip_prefix("0x").unwrap_or(value);
+    u16::from_str_radix(digits, 16).ok()
+}